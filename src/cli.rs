@@ -1,4 +1,5 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use std::net::IpAddr;
 
 #[derive(Clone, Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -11,15 +12,142 @@ pub struct Cli {
     #[arg(short, long, default_value = ".flowsnet.env")]
     pub env_file: String,
 
-    /// Flow identity in flows.network
-    #[arg(short, long)]
+    /// Flow identity in flows.network, not needed with `--replay`
+    #[arg(
+        short,
+        long,
+        required_unless_present = "replay",
+        default_value = "",
+        hide_default_value = true
+    )]
     pub flow: String,
 
     /// Wasm file path in the local file system
     #[arg(short, long)]
     pub wasm: String,
 
-    /// Port of the local service
-    #[arg(short, long)]
+    /// Port of the local service, not needed with `--replay`
+    #[arg(
+        short,
+        long,
+        required_unless_present = "replay",
+        default_value_t = 0,
+        hide_default_value = true
+    )]
     pub port: u16,
+
+    /// Run the exported function with `is_listening` set before serving and print
+    /// the triggers it registers
+    #[arg(long, value_name = "FUNC", num_args = 0..=1, default_missing_value = "on_deploy")]
+    pub register: Option<String>,
+
+    /// TOML file of cron triggers to fire against the local handlers
+    #[arg(long, value_name = "FILE")]
+    pub schedule: Option<String>,
+
+    /// Directory to persist every incoming request and its response in
+    #[arg(long, value_name = "DIR")]
+    pub record_dir: Option<String>,
+
+    /// Re-run an event recorded with `--record-dir` against the wasm, print the
+    /// difference to the recorded response and exit
+    #[arg(long, value_name = "EVENT_FILE")]
+    pub replay: Option<String>,
+
+    /// Cassette file of the guest's outbound HTTPS exchanges
+    #[arg(long, value_name = "FILE")]
+    pub cassette: Option<String>,
+
+    /// Whether to record outbound HTTPS exchanges into the cassette or answer them from it
+    #[arg(long, value_enum, default_value_t = CassetteMode::Replay)]
+    pub cassette_mode: CassetteMode,
+
+    /// TOML file of stub responses for the guest's outbound HTTPS calls
+    #[arg(long, value_name = "FILE")]
+    pub mock_rules: Option<String>,
+
+    /// Directory to dump the raw bytes of every outbound request/response pair in
+    #[arg(long, value_name = "DIR")]
+    pub dump_outbound: Option<String>,
+
+    /// TOML file restricting which hosts and ports the guest may connect to
+    #[arg(long, value_name = "FILE")]
+    pub network_policy: Option<String>,
+
+    /// PEM bundle of CA certificates the guest's TLS connections trust, can be repeated
+    #[arg(long, value_name = "PEM")]
+    pub ca_cert: Vec<String>,
+
+    /// Also trust the operating system's root certificates in the guest's TLS connections
+    #[arg(long)]
+    pub system_roots: bool,
+
+    /// PEM client certificate chain presented by the guest's TLS connections
+    #[arg(long, value_name = "PEM", requires = "client_key")]
+    pub client_cert: Option<String>,
+
+    /// PEM private key of the client certificate
+    #[arg(long, value_name = "PEM", requires = "client_cert")]
+    pub client_key: Option<String>,
+
+    /// Seconds to wait for a guest outbound connection to be established
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    pub outbound_connect_timeout: u64,
+
+    /// Seconds to wait for the TLS handshake of a guest outbound connection
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    pub outbound_handshake_timeout: u64,
+
+    /// Seconds to wait for the next bytes of a guest outbound response
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub outbound_read_timeout: u64,
+
+    /// Largest guest outbound response in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024 * 1024)]
+    pub outbound_max_response_size: usize,
+
+    /// Largest request body accepted by the handlers in bytes, larger ones get a 413;
+    /// at most 2147483647, the guest gets the length as an `i32`
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = 10 * 1024 * 1024,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=i32::MAX as u64)
+    )]
+    pub body_limit: usize,
+
+    /// HTTP proxy for platform API calls and the guest's TLS connections,
+    /// overrides `HTTPS_PROXY`
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Comma separated hosts reached without the proxy, overrides `NO_PROXY`
+    #[arg(long, value_name = "HOSTS")]
+    pub no_proxy: Option<String>,
+
+    /// Number of recent invocations kept for the dashboard on `/_flowsnet/ui`,
+    /// only served to local clients with the token printed at startup; 0 turns it off
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub dashboard_size: usize,
+
+    /// Address of a reverse proxy whose forwarding headers are trusted besides
+    /// the local tunnel's, can be repeated
+    #[arg(long, value_name = "IP")]
+    pub trusted_proxy: Vec<IpAddr>,
+
+    /// Output format of the log records
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CassetteMode {
+    Record,
+    Replay,
 }
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+
+use crate::cli::CassetteMode;
+use crate::executor::base64_bytes;
+
+/// One outbound exchange made through `wasmedge_httpsreq_send_data`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Exchange {
+    pub host: String,
+    pub port: u16,
+    #[serde(with = "base64_bytes")]
+    pub request: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub response: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct CassetteFile {
+    exchanges: Vec<Exchange>,
+}
+
+/// Stores the guest's outbound HTTPS exchanges in a file, or answers them from it,
+/// so flows can be tested without touching the network.
+///
+/// Exchanges are recorded as JSON lines, one appended per call; cassettes
+/// written as a single `{"exchanges": [...]}` document can still be replayed.
+#[derive(Debug)]
+pub struct Cassette {
+    mode: CassetteMode,
+    /// The file being recorded into
+    file: Option<tokio::sync::Mutex<tokio::fs::File>>,
+    exchanges: Mutex<Vec<(Exchange, bool)>>,
+}
+
+impl Cassette {
+    /// In record mode the file is started over, in replay mode it must exist.
+    pub fn open<P: AsRef<Path>>(path: P, mode: CassetteMode) -> anyhow::Result<Self> {
+        let (file, exchanges) = match mode {
+            CassetteMode::Record => {
+                let file = std::fs::File::create(path)?;
+                let file = tokio::fs::File::from_std(file);
+                (Some(tokio::sync::Mutex::new(file)), vec![])
+            }
+            CassetteMode::Replay => (None, load(&std::fs::read(path)?)?),
+        };
+
+        Ok(Self {
+            mode,
+            file,
+            exchanges: Mutex::new(exchanges.into_iter().map(|e| (e, false)).collect()),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub async fn record(&self, exchange: &Exchange) -> anyhow::Result<()> {
+        let file = self
+            .file
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the cassette is not opened for recording"))?;
+        let mut line = serde_json::to_vec(exchange)?;
+        line.push(b'\n');
+
+        // held for the whole line so concurrent runs don't interleave
+        let mut file = file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Answer with the first recorded exchange matching the request which has not
+    /// been played yet, falling back to one already played.
+    pub fn replay(&self, host: &str, port: u16, request: &[u8]) -> Option<Vec<u8>> {
+        let mut exchanges = self.exchanges.lock().ok()?;
+        let matches = |e: &Exchange| e.host == host && e.port == port && e.request == request;
+
+        let idx = exchanges
+            .iter()
+            .position(|(e, played)| !played && matches(e))
+            .or_else(|| exchanges.iter().position(|(e, _)| matches(e)))?;
+
+        let (exchange, played) = &mut exchanges[idx];
+        *played = true;
+        Some(exchange.response.clone())
+    }
+}
+
+/// Exchanges of a JSON lines cassette, or of one written as a single document
+fn load(data: &[u8]) -> anyhow::Result<Vec<Exchange>> {
+    if let Ok(file) = serde_json::from_slice::<CassetteFile>(data) {
+        return Ok(file.exchanges);
+    }
+    data.split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| serde_json::from_slice(line).map_err(anyhow::Error::from))
+        .collect()
+}
@@ -0,0 +1,87 @@
+use axum::http::{header, HeaderMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+static REQUEST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Who sent an event and how, from the connection and the forwarding headers
+/// set by the platform's tunnel or a trusted reverse proxy in front.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    /// IP address of the original caller
+    pub remote_addr: String,
+    pub host: String,
+    pub scheme: String,
+    /// From `X-Request-Id`, or generated when the request has none
+    pub request_id: String,
+}
+
+impl ClientInfo {
+    /// Forwarding headers are only honoured when `peer` is the local tunnel or
+    /// one of `trusted_proxies`, anyone else could fake them.
+    pub fn new(peer: Option<SocketAddr>, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> Self {
+        let trusted = peer
+            .is_some_and(|peer| peer.ip().is_loopback() || trusted_proxies.contains(&peer.ip()));
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let forwarding = |name: &str| header(name).filter(|_| trusted);
+        let forwarded = |key: &str| forwarding("forwarded").and_then(|v| forwarded_param(v, key));
+
+        // the left-most entry is the original caller
+        let remote_addr = forwarding("x-forwarded-for")
+            .and_then(|v| v.split(',').next())
+            .map(|v| v.trim().to_string())
+            .or_else(|| forwarded("for"))
+            .or_else(|| peer.map(|peer| peer.ip().to_string()))
+            .unwrap_or_default();
+        let host = forwarding("x-forwarded-host")
+            .map(str::to_string)
+            .or_else(|| forwarded("host"))
+            .or_else(|| header(header::HOST.as_str()).map(str::to_string))
+            .unwrap_or_default();
+        let scheme = forwarding("x-forwarded-proto")
+            .map(str::to_string)
+            .or_else(|| forwarded("proto"))
+            .unwrap_or_else(|| String::from("http"));
+        let request_id = header(REQUEST_ID_HEADER)
+            .map(str::to_string)
+            .unwrap_or_else(generate_request_id);
+
+        Self {
+            remote_addr,
+            host,
+            scheme,
+            request_id,
+        }
+    }
+}
+
+/// Value of `key` in the first element of a `Forwarded` header, e.g. `for` in
+/// `for="[2001:db8::1]:4711";proto=https`
+fn forwarded_param(forwarded: &str, key: &str) -> Option<String> {
+    let first = forwarded.split(',').next()?;
+    first.split(';').find_map(|pair| {
+        let (k, v) = pair.split_once('=')?;
+        k.trim()
+            .eq_ignore_ascii_case(key)
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// 32 hex digits from the clock, the process and a counter
+pub fn generate_request_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let seq = REQUEST_SEQ.fetch_add(1, Ordering::Relaxed);
+    format!("{nanos:016x}{:08x}{:08x}", std::process::id(), seq as u32)
+}
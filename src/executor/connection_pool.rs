@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use crate::executor::tls_wrap_plugin::Transport;
+
+const MAX_IDLE_PER_HOST: usize = 4;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An open connection to a remote, kept between the guest's outbound calls.
+pub enum PooledStream {
+    Tls(Box<TlsStream<TcpStream>>),
+    Plain(TcpStream),
+}
+
+impl AsyncRead for PooledStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for PooledStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+            Self::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+type PoolKey = (Transport, String, u16);
+
+/// Idle connections per remote, shared by every run of the flow.
+#[derive(Default)]
+pub struct ConnectionPool {
+    idle: Mutex<HashMap<PoolKey, Vec<(PooledStream, Instant)>>>,
+}
+
+impl fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let idle = self
+            .idle
+            .lock()
+            .map(|idle| idle.values().map(Vec::len).sum::<usize>())
+            .unwrap_or_default();
+        f.debug_struct("ConnectionPool")
+            .field("idle", &idle)
+            .finish()
+    }
+}
+
+impl ConnectionPool {
+    /// The most recently used idle connection which has not expired
+    pub fn take(&self, transport: Transport, host: &str, port: u16) -> Option<PooledStream> {
+        let mut idle = self.idle.lock().ok()?;
+        let conns = idle.get_mut(&(transport, host.to_string(), port))?;
+        conns.retain(|(_, since)| since.elapsed() < IDLE_TIMEOUT);
+        conns.pop().map(|(stream, _)| stream)
+    }
+
+    pub fn put(&self, transport: Transport, host: &str, port: u16, stream: PooledStream) {
+        if let Ok(mut idle) = self.idle.lock() {
+            let conns = idle.entry((transport, host.to_string(), port)).or_default();
+            if conns.len() >= MAX_IDLE_PER_HOST {
+                conns.remove(0);
+            }
+            conns.push((stream, Instant::now()));
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::Html,
+    Json,
+};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::executor::client_info::{self, ClientInfo};
+use crate::executor::event_store::RecordedEvent;
+use crate::executor::invocation::InvocationRecord;
+use crate::executor::local_access::header_token;
+use crate::executor::outbound_log::{self, OutboundCallRecord};
+use crate::executor::read_write_buf::ReadWriteBuf;
+use crate::executor::{handler_inner, AppState, FlowEvent};
+
+/// Request and response bodies kept per invocation, larger ones are dropped
+pub const BODY_LIMIT: usize = 256 * 1024;
+/// Guest stdout and stderr kept per invocation
+pub const STDIO_LIMIT: usize = 64 * 1024;
+
+const PAGE: &str = include_str!("dashboard.html");
+
+/// What a run leaves besides its response: the guest's stdio, its outbound
+/// calls and how long the wasm ran.
+#[derive(Default)]
+pub struct RunCapture {
+    pub stdout: ReadWriteBuf,
+    pub stderr: ReadWriteBuf,
+    pub outbound: Arc<Mutex<Vec<OutboundCallRecord>>>,
+    pub wasm_ms: u64,
+}
+
+/// One handler run as shown on `/_flowsnet/ui`
+#[derive(Clone, Debug, Serialize)]
+pub struct DashboardEntry {
+    pub id: String,
+    /// Id of the invocation this one replayed
+    pub replay_of: Option<String>,
+    pub invocation: InvocationRecord,
+    /// The request, with the response it got. Kept as received for replays,
+    /// shown with the credentials in its headers and the query values redacted.
+    #[serde(serialize_with = "redacted_event")]
+    pub event: RecordedEvent,
+    /// The request body was over `BODY_LIMIT`, so it can't be replayed
+    pub body_dropped: bool,
+    pub response_body_dropped: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub outbound: Vec<OutboundCallRecord>,
+    pub wasm_ms: u64,
+}
+
+#[derive(Serialize)]
+struct EntrySummary<'a> {
+    id: &'a str,
+    replay_of: Option<&'a str>,
+    outbound_calls: usize,
+    #[serde(flatten)]
+    invocation: &'a InvocationRecord,
+}
+
+impl DashboardEntry {
+    /// `body_len` is the size of the request body, which `event` only carries
+    /// when it is within `BODY_LIMIT` or recorded anyway
+    pub fn new(
+        record: &InvocationRecord,
+        event: &RecordedEvent,
+        body_len: u64,
+        capture: RunCapture,
+    ) -> Self {
+        let mut event = event.clone();
+        let body_dropped = body_len > BODY_LIMIT as u64;
+        if body_dropped {
+            event.body = vec![];
+        }
+        let mut response_body_dropped = false;
+        if let Some(response) = event.response.as_mut() {
+            if response.body.len() > BODY_LIMIT {
+                response.body = vec![];
+                response_body_dropped = true;
+            }
+        }
+        let outbound = capture
+            .outbound
+            .lock()
+            .map(|calls| calls.clone())
+            .unwrap_or_default();
+
+        Self {
+            id: event.id.clone(),
+            replay_of: None,
+            invocation: record.clone(),
+            event,
+            body_dropped,
+            response_body_dropped,
+            stdout: capture.stdout.read_all(),
+            stderr: capture.stderr.read_all(),
+            outbound,
+            wasm_ms: capture.wasm_ms,
+        }
+    }
+}
+
+fn redacted_event<S: Serializer>(event: &RecordedEvent, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut event = event.clone();
+    event.raw_query = outbound_log::redact_query_values(&event.raw_query);
+    for (_, value) in event.query_pairs.iter_mut() {
+        *value = String::from("<redacted>");
+    }
+    for value in event.query.values_mut() {
+        *value = Value::from("<redacted>");
+    }
+    for (name, value) in event.raw_headers.iter_mut() {
+        *value = outbound_log::redact(name, value).into_bytes();
+    }
+    if let Some(response) = event.response.as_mut() {
+        for (name, value) in response.headers.iter_mut() {
+            *value = outbound_log::redact(name, value.as_bytes());
+        }
+    }
+    event.serialize(serializer)
+}
+
+/// Ring buffer of the most recent invocations
+pub struct Dashboard {
+    capacity: usize,
+    entries: RwLock<VecDeque<DashboardEntry>>,
+}
+
+impl Dashboard {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: RwLock::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn push(&self, entry: DashboardEntry) {
+        if let Ok(mut entries) = self.entries.write() {
+            if entries.len() >= self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<DashboardEntry> {
+        let entries = self.entries.read().ok()?;
+        entries.iter().find(|entry| entry.id == id).cloned()
+    }
+
+    /// Link the run with `request_id` to the invocation it replayed and
+    /// return its id
+    fn mark_replay(&self, request_id: &str, replay_of: &str) -> Option<String> {
+        let mut entries = self.entries.write().ok()?;
+        let entry = entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.invocation.request_id == request_id)?;
+        entry.replay_of = Some(replay_of.to_string());
+        Some(entry.id.clone())
+    }
+}
+
+type ApiError = (StatusCode, String);
+
+/// The dashboard, for a local `peer` presenting the access token
+fn dashboard<'a>(
+    state: &'a AppState,
+    peer: &SocketAddr,
+    token: Option<&str>,
+) -> Result<&'a Dashboard, ApiError> {
+    let dashboard = state.dashboard.as_deref().ok_or((
+        StatusCode::NOT_FOUND,
+        String::from("the dashboard is off, turn it on with `--dashboard-size`"),
+    ))?;
+    state.local_access.check(peer, token)?;
+    Ok(dashboard)
+}
+
+pub async fn page(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Html<&'static str>, ApiError> {
+    dashboard(&state, &peer, query.get("token").map(String::as_str))?;
+    Ok(Html(PAGE))
+}
+
+/// Summaries of the buffered invocations, newest first
+pub async fn list(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, ApiError> {
+    let entries = dashboard(&state, &peer, header_token(&headers))?
+        .entries
+        .read()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, String::new()))?;
+    let summaries = entries
+        .iter()
+        .rev()
+        .map(|entry| EntrySummary {
+            id: &entry.id,
+            replay_of: entry.replay_of.as_deref(),
+            outbound_calls: entry.outbound.len(),
+            invocation: &entry.invocation,
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(serde_json::to_value(summaries).unwrap_or_default()))
+}
+
+pub async fn detail(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<DashboardEntry>, ApiError> {
+    dashboard(&state, &peer, header_token(&headers))?
+        .get(&id)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("no invocation {id}")))
+}
+
+/// Run the request of an invocation again, the new run shows up as its own entry
+pub async fn replay(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let dashboard = dashboard(&state, &peer, header_token(&headers))?;
+    let entry = dashboard
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, format!("no invocation {id}")))?;
+    if entry.body_dropped {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("the request body of {id} was too large to keep, it can't be replayed"),
+        ));
+    }
+    let event = entry.event;
+    let method = Method::from_bytes(event.method.as_bytes())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let headers = event.header_map();
+    // a fresh id, so the replay can be told apart from the original
+    let client = ClientInfo {
+        request_id: client_info::generate_request_id(),
+        ..ClientInfo::new(None, &headers, &[])
+    };
+    let request_id = client.request_id.clone();
+
+    let flow_event = FlowEvent {
+        flows_user: event.flows_user.clone(),
+        handler: event.handler.clone(),
+        method,
+        headers,
+        subpath: event.subpath.clone(),
+        query: event.event_query(),
+        body: Bytes::from(event.body).into(),
+        client,
+    };
+    let (status, _, _) = handler_inner(state.clone(), flow_event, None).await;
+
+    let replayed = dashboard.mark_replay(&request_id, &id);
+    Ok(Json(serde_json::json!({
+        "id": replayed,
+        "status": status.as_u16(),
+    })))
+}
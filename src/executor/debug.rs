@@ -0,0 +1,101 @@
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, LinkedList};
+use std::net::SocketAddr;
+
+use crate::executor::local_access::header_token;
+use crate::executor::AppState;
+use crate::health::{self, Health};
+use crate::metrics::METRICS;
+
+/// What the guest handed over through `set_output` and `set_flows` in one run.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FlowOutput {
+    pub handler: String,
+    pub output: Vec<String>,
+    pub flows: Option<Value>,
+}
+
+impl FlowOutput {
+    pub fn new(handler: &str, output: &LinkedList<Vec<u8>>, flows: Option<&str>) -> Self {
+        Self {
+            handler: handler.to_string(),
+            output: output
+                .iter()
+                .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+                .collect(),
+            flows: flows.map(check_flows),
+        }
+    }
+}
+
+/// Validate and print the flows JSON registered by the guest.
+/// Invalid JSON is kept as a raw string so it still shows up in the debug endpoint.
+pub fn check_flows(flows: &str) -> Value {
+    match serde_json::from_str::<Value>(flows) {
+        Ok(v) => {
+            tracing::info!(
+                target: "flowsnet::flows",
+                "flows registered:\n{}",
+                serde_json::to_string_pretty(&v).unwrap_or_default()
+            );
+            v
+        }
+        Err(e) => {
+            tracing::warn!(
+                target: "flowsnet::flows",
+                "flows registered by the guest is not valid JSON ({e}): {flows}"
+            );
+            Value::String(flows.to_string())
+        }
+    }
+}
+
+/// Only for local clients with the access token, the output may hold anything
+pub async fn last_output(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Json<Option<FlowOutput>>, (StatusCode, String)> {
+    let token = header_token(&headers).or(query.get("token").map(String::as_str));
+    state.local_access.check(&peer, token)?;
+    Ok(Json(
+        state.last_output.read().ok().and_then(|last| last.clone()),
+    ))
+}
+
+/// Liveness: answering at all means the executor is up
+pub async fn healthz() -> Json<Health> {
+    Json(health::snapshot())
+}
+
+/// Readiness: the wasm loads, the tunnel is up and the last heartbeat went through
+pub async fn readyz() -> (StatusCode, Json<Value>) {
+    let health = health::snapshot();
+    let reasons = health.not_ready();
+    let status = match reasons.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let body = serde_json::json!({
+        "ready": reasons.is_empty(),
+        "reasons": reasons,
+        "health": health,
+    });
+    (status, Json(body))
+}
+
+/// Prometheus text exposition of `METRICS`
+pub async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+}
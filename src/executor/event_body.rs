@@ -0,0 +1,166 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    http::StatusCode,
+};
+use std::borrow::Cow;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+
+/// Bodies larger than this are spilled to a temporary file
+const SPILL_THRESHOLD: usize = 1024 * 1024;
+
+static SPOOL_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Request body of an event, kept in memory or, when large, in a temporary file
+/// the guest reads it from in chunks.
+#[derive(Debug)]
+pub enum EventBody {
+    Memory(Bytes),
+    File(SpooledFile),
+}
+
+/// Removed when the event is done with
+#[derive(Debug)]
+pub struct SpooledFile {
+    path: PathBuf,
+    len: u64,
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl From<Bytes> for EventBody {
+    fn from(bytes: Bytes) -> Self {
+        Self::Memory(bytes)
+    }
+}
+
+impl Default for EventBody {
+    fn default() -> Self {
+        Self::Memory(Bytes::new())
+    }
+}
+
+impl EventBody {
+    /// Read the request body, refusing it with `413` once it grows past `limit`.
+    pub async fn from_stream(
+        mut stream: Body,
+        content_length: Option<u64>,
+        limit: usize,
+    ) -> Result<Self, StatusCode> {
+        if content_length.is_some_and(|len| len > limit as u64) {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        let mut buf = Vec::new();
+        let mut file: Option<(tokio::fs::File, SpooledFile)> = None;
+        let mut len = 0;
+        while let Some(chunk) = stream.data().await {
+            let chunk = chunk.map_err(|e| {
+                tracing::warn!("Failed to read the request body: {e}");
+                StatusCode::BAD_REQUEST
+            })?;
+            len += chunk.len();
+            if len > limit {
+                return Err(StatusCode::PAYLOAD_TOO_LARGE);
+            }
+
+            if file.is_none() && len > SPILL_THRESHOLD {
+                file = Some(spool_file(&buf).await.map_err(spool_error)?);
+                buf = Vec::new();
+            }
+            match file.as_mut() {
+                Some((file, _)) => file.write_all(&chunk).await.map_err(spool_error)?,
+                None => buf.extend_from_slice(&chunk),
+            }
+        }
+
+        match file {
+            Some((mut file, mut spooled)) => {
+                file.flush().await.map_err(spool_error)?;
+                spooled.len = len as u64;
+                Ok(Self::File(spooled))
+            }
+            None => Ok(Self::Memory(Bytes::from(buf))),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Memory(bytes) => bytes.len() as u64,
+            Self::File(file) => file.len,
+        }
+    }
+
+    /// The whole body at once, for `get_event_body`
+    pub fn read_all(&self) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Memory(bytes) => Ok(Cow::Borrowed(bytes.as_ref())),
+            Self::File(file) => std::fs::read(&file.path).map(Cow::Owned),
+        }
+    }
+
+    pub async fn to_bytes(&self) -> std::io::Result<Bytes> {
+        match self {
+            Self::Memory(bytes) => Ok(bytes.clone()),
+            Self::File(file) => tokio::fs::read(&file.path).await.map(Bytes::from),
+        }
+    }
+
+    /// A reader from the start of the body, for `read_event_body_chunk`
+    pub fn reader(&self) -> std::io::Result<EventBodyReader> {
+        match self {
+            Self::Memory(bytes) => Ok(EventBodyReader::Memory(Cursor::new(bytes.clone()))),
+            Self::File(file) => std::fs::File::open(&file.path).map(EventBodyReader::File),
+        }
+    }
+}
+
+pub enum EventBodyReader {
+    Memory(Cursor<Bytes>),
+    File(std::fs::File),
+}
+
+impl Read for EventBodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Memory(cursor) => cursor.read(buf),
+            Self::File(file) => file.read(buf),
+        }
+    }
+}
+
+async fn spool_file(head: &[u8]) -> std::io::Result<(tokio::fs::File, SpooledFile)> {
+    let mut attempts = 0;
+    let (path, mut file) = loop {
+        let seq = SPOOL_SEQ.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("flowsnet-body-{}-{seq}", std::process::id()));
+        // never follow a link or reuse a file someone else put there, and keep
+        // the body from other users
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path).await {
+            Ok(file) => break (path, file),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 16 => {
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    // removes the file again should the rest of the body fail
+    let spooled = SpooledFile { path, len: 0 };
+    file.write_all(head).await?;
+    Ok((file, spooled))
+}
+
+fn spool_error(e: std::io::Error) -> StatusCode {
+    tracing::warn!("Failed to spool the request body: {e}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
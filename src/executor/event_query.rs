@@ -0,0 +1,53 @@
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// Query string of an event, kept the way the caller sent it.
+#[derive(Clone, Debug, Default)]
+pub struct EventQuery {
+    /// Without the leading `?`
+    pub raw: String,
+    /// Decoded pairs in their order, repeated keys included
+    pub pairs: Vec<(String, String)>,
+}
+
+impl EventQuery {
+    pub fn new(raw: Option<String>, pairs: Vec<(String, String)>) -> Self {
+        Self {
+            raw: raw.unwrap_or_default(),
+            pairs,
+        }
+    }
+
+    /// For events with only the legacy map at hand, e.g. older recorded events
+    pub fn from_map(map: &HashMap<String, Value>) -> Self {
+        let pairs = map
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(value) => (key.clone(), value.clone()),
+                value => (key.clone(), value.to_string()),
+            })
+            .collect();
+        Self {
+            raw: String::new(),
+            pairs,
+        }
+    }
+
+    /// The map `get_event_query` has always returned, the last value of a
+    /// repeated key wins
+    pub fn map(&self) -> HashMap<String, Value> {
+        self.pairs
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect()
+    }
+
+    /// Every value of every key, e.g. `{"tag": ["a", "b"]}`
+    pub fn multi_map(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut multi = BTreeMap::<&str, Vec<&str>>::new();
+        for (key, value) in self.pairs.iter() {
+            multi.entry(key).or_default().push(value);
+        }
+        multi
+    }
+}
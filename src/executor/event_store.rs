@@ -0,0 +1,260 @@
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+use crate::executor::base64_bytes;
+use crate::executor::client_info::ClientInfo;
+use crate::executor::event_query::EventQuery;
+use crate::executor::{handler_inner, AppState, FlowEvent};
+
+static EVENT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// An incoming request persisted by `--record-dir`, together with the response it got.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub id: String,
+    pub timestamp: u64,
+    pub flows_user: String,
+    /// Handler name from the request path
+    pub handler: String,
+    /// Exported wasm function which handled the request
+    pub handler_fn: String,
+    pub method: String,
+    pub subpath: String,
+    pub query: HashMap<String, Value>,
+    /// Absent from events recorded by older versions
+    #[serde(default)]
+    pub raw_query: String,
+    #[serde(default)]
+    pub query_pairs: Vec<(String, String)>,
+    /// Values in base64, so opaque bytes survive a replay
+    #[serde(default, with = "base64_bytes::pairs")]
+    pub raw_headers: Vec<(String, Vec<u8>)>,
+    /// Header values as text, from events recorded by older versions
+    #[serde(default, skip_serializing)]
+    headers: Vec<(String, String)>,
+    #[serde(with = "base64_bytes")]
+    pub body: Vec<u8>,
+    pub response: Option<RecordedResponse>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(with = "base64_bytes")]
+    pub body: Vec<u8>,
+}
+
+impl RecordedResponse {
+    pub fn new(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Self {
+        Self {
+            status: status.as_u16(),
+            headers: header_pairs(headers),
+            body: body.to_vec(),
+        }
+    }
+}
+
+impl RecordedEvent {
+    pub fn new(
+        flows_user: &str,
+        handler: &str,
+        method: &Method,
+        headers: &HeaderMap,
+        subpath: &str,
+        query: &EventQuery,
+        body: &[u8],
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let seq = EVENT_SEQ.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            id: format!("{timestamp}-{seq}"),
+            timestamp,
+            flows_user: flows_user.to_string(),
+            handler: handler.to_string(),
+            handler_fn: String::new(),
+            method: method.as_str().to_string(),
+            subpath: subpath.to_string(),
+            query: query.map(),
+            raw_query: query.raw.clone(),
+            query_pairs: query.pairs.clone(),
+            raw_headers: headers
+                .iter()
+                .map(|(k, v)| (k.as_str().to_string(), v.as_bytes().to_vec()))
+                .collect(),
+            headers: vec![],
+            body: body.to_vec(),
+            response: None,
+        }
+    }
+
+    pub fn event_query(&self) -> EventQuery {
+        match self.query_pairs.is_empty() {
+            true => EventQuery::from_map(&self.query),
+            false => EventQuery {
+                raw: self.raw_query.clone(),
+                pairs: self.query_pairs.clone(),
+            },
+        }
+    }
+
+    pub fn header_map(&self) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        for (name, value) in self.raw_headers.iter() {
+            if let (Ok(hn), Ok(hv)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_bytes(value),
+            ) {
+                h.append(hn, hv);
+            }
+        }
+        h
+    }
+
+    pub async fn save<P: AsRef<Path>>(&self, dir: P) -> anyhow::Result<PathBuf> {
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir).await?;
+        let path = dir.join(format!("{}.json", self.id));
+        // the headers carry credentials, keep them from other users and never
+        // write through a link someone else put there
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&path).await?;
+        file.write_all(&serde_json::to_vec_pretty(self)?).await?;
+        file.flush().await?;
+        Ok(path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)?;
+        let mut event: Self = serde_json::from_slice(&data)?;
+        let legacy = std::mem::take(&mut event.headers);
+        event.raw_headers.extend(
+            legacy
+                .into_iter()
+                .map(|(name, value)| (name, value.into_bytes())),
+        );
+        Ok(event)
+    }
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            (
+                k.as_str().to_string(),
+                String::from_utf8_lossy(v.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Re-run a recorded event against the current wasm and print how the new
+/// response differs from the recorded one.
+pub async fn replay<P: AsRef<Path>>(state: AppState, path: P) -> anyhow::Result<()> {
+    let event = RecordedEvent::load(path)?;
+    let method = Method::from_bytes(event.method.as_bytes())?;
+
+    let flow_event = FlowEvent {
+        flows_user: event.flows_user.clone(),
+        handler: event.handler.clone(),
+        method,
+        headers: event.header_map(),
+        subpath: event.subpath.clone(),
+        query: event.event_query(),
+        body: Bytes::from(event.body.clone()).into(),
+        client: ClientInfo::new(None, &event.header_map(), &[]),
+    };
+    let (status, headers, body) = handler_inner(state, flow_event, None).await;
+    let replayed = RecordedResponse::new(status, &headers, &body);
+
+    println!(
+        "Replayed event {} ({} /{}/{}{})",
+        event.id,
+        event.method,
+        event.flows_user,
+        event.handler,
+        if event.subpath == "/" {
+            String::new()
+        } else {
+            format!("/{}", event.subpath)
+        }
+    );
+
+    match event.response.as_ref() {
+        Some(recorded) => print_diff(recorded, &replayed),
+        None => println!("No response was recorded for this event, nothing to diff against"),
+    }
+
+    Ok(())
+}
+
+fn print_diff(recorded: &RecordedResponse, replayed: &RecordedResponse) {
+    let mut same = true;
+
+    if recorded.status != replayed.status {
+        same = false;
+        println!("status: {} -> {}", recorded.status, replayed.status);
+    }
+
+    let recorded_headers = header_index(&recorded.headers);
+    let replayed_headers = header_index(&replayed.headers);
+    for (name, old) in recorded_headers.iter() {
+        match replayed_headers.get(name) {
+            Some(new) if new == old => {}
+            Some(new) => {
+                same = false;
+                println!("header {name}: {old:?} -> {new:?}");
+            }
+            None => {
+                same = false;
+                println!("header {name}: {old:?} -> (removed)");
+            }
+        }
+    }
+    for (name, new) in replayed_headers.iter() {
+        if !recorded_headers.contains_key(name) {
+            same = false;
+            println!("header {name}: (added) -> {new:?}");
+        }
+    }
+
+    if recorded.body != replayed.body {
+        same = false;
+        println!(
+            "body ({} bytes -> {} bytes):\n--- recorded\n{}\n+++ replayed\n{}",
+            recorded.body.len(),
+            replayed.body.len(),
+            String::from_utf8_lossy(&recorded.body),
+            String::from_utf8_lossy(&replayed.body)
+        );
+    }
+
+    if same {
+        println!("Response matches the recorded one");
+    }
+}
+
+fn header_index(headers: &[(String, String)]) -> BTreeMap<&str, Vec<&str>> {
+    let mut index: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (name, value) in headers.iter() {
+        index.entry(name.as_str()).or_default().push(value.as_str());
+    }
+    index
+}
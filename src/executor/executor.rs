@@ -1,23 +1,29 @@
 use axum::{
-    body::Bytes,
-    extract::{DefaultBodyLimit, Path, Query, State},
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Path, Query, RawQuery, State},
     http::{
-        header::{HeaderMap, HeaderName, HeaderValue},
-        Method, StatusCode,
+        header::{self, HeaderMap, HeaderName, HeaderValue},
+        Method, Request, StatusCode,
     },
-    response::IntoResponse,
-    routing::any,
+    response::{IntoResponse, Response},
+    routing::{any, get, post},
     Router, Server,
 };
-use host_func::FlowsParams;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use host_func::{FlowsParams, ResponseChunk};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::LinkedList;
+use std::io::Write;
 use std::net::SocketAddr;
 use std::path::{self, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Once, RwLock};
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, fs};
-use tokio::sync::broadcast;
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, Sender},
+};
 use wasmedge_sdk::{
     config::{CommonConfigOptions, ConfigBuilder},
     params,
@@ -32,13 +38,125 @@ use wasmedge_sdk::{
     ExternalInstanceType, ImportObject, Module, Store, WasmEdgeResult,
 };
 
+use crate::executor::cassette::Cassette;
+use crate::executor::client_info::ClientInfo;
+use crate::executor::dashboard::{self, Dashboard, DashboardEntry, RunCapture};
+use crate::executor::debug::{self, FlowOutput};
+use crate::executor::event_body::EventBody;
+use crate::executor::event_query::EventQuery;
+use crate::executor::event_store::{self, RecordedEvent, RecordedResponse};
 use crate::executor::flow_file_sys;
 use crate::executor::host_func;
-use crate::executor::tls_wrap_plugin;
+use crate::executor::http_wrap_plugin;
+use crate::executor::invocation::InvocationRecord;
+use crate::executor::local_access::LocalAccess;
+use crate::executor::mock::MockRules;
+use crate::executor::network_policy::NetworkPolicy;
+use crate::executor::outbound_log::OutboundDump;
+use crate::executor::read_write_buf::TeeWriter;
+use crate::executor::schedule;
+use crate::executor::tls_wrap_plugin::{self, OutboundLimits, TlsOptions, WasmEdgeTlsReqData};
+use crate::health::{self, WasmStatus};
+use crate::metrics::METRICS;
+use crate::proxy::ProxyConfig;
 use crate::Cli;
 
+const RESERVED_PREFIX: &str = "_flowsnet";
+const FLOWS_ERROR_CODE_HEADER: &str = "x-flows-error-code";
+/// Response chunks a streaming guest may be ahead of the client
+const RESPONSE_CHUNK_BUFFER: usize = 16;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub cli: Cli,
+    pub last_output: Arc<RwLock<Option<FlowOutput>>>,
+    /// Template for the `wasmedge_httpsreq` host data of every run
+    pub https_data: WasmEdgeTlsReqData,
+    /// Recent invocations for `/_flowsnet/ui`, `None` with `--dashboard-size 0`
+    pub dashboard: Option<Arc<Dashboard>>,
+    /// Guards `/_flowsnet/ui` and `/_flowsnet/debug/output`
+    pub local_access: Arc<LocalAccess>,
+}
+
+impl AppState {
+    pub fn new(cli: Cli) -> anyhow::Result<Self> {
+        let tls_options = TlsOptions {
+            ca_certs: cli.ca_cert.iter().map(PathBuf::from).collect(),
+            system_roots: cli.system_roots,
+            client_cert: cli
+                .client_cert
+                .as_ref()
+                .zip(cli.client_key.as_ref())
+                .map(|(cert, key)| (PathBuf::from(cert), PathBuf::from(key))),
+        };
+        let client_config = tls_wrap_plugin::client_config(&tls_options)
+            .map_err(|e| anyhow::anyhow!("Failed to set up TLS: {e}"))?;
+
+        let limits = OutboundLimits {
+            connect_timeout: Duration::from_secs(cli.outbound_connect_timeout),
+            handshake_timeout: Duration::from_secs(cli.outbound_handshake_timeout),
+            read_timeout: Duration::from_secs(cli.outbound_read_timeout),
+            max_response_size: cli.outbound_max_response_size,
+        };
+
+        let mut https_data = WasmEdgeTlsReqData::new(client_config).with_limits(limits);
+        if let Some(cassette) = cli.cassette.as_deref() {
+            let cassette = Cassette::open(cassette, cli.cassette_mode)
+                .map_err(|e| anyhow::anyhow!("Failed to open cassette {cassette}: {e}"))?;
+            https_data = https_data.with_cassette(Arc::new(cassette));
+        }
+        if let Some(mock_rules) = cli.mock_rules.as_deref() {
+            let rules = MockRules::load(mock_rules)
+                .map_err(|e| anyhow::anyhow!("Failed to load mock rules {mock_rules}: {e}"))?;
+            https_data = https_data.with_mock_rules(Arc::new(rules));
+        }
+        if let Some(dump_dir) = cli.dump_outbound.as_deref() {
+            let dump = OutboundDump::new(dump_dir)
+                .map_err(|e| anyhow::anyhow!("Failed to create dump directory {dump_dir}: {e}"))?;
+            https_data = https_data.with_dump(Arc::new(dump));
+        }
+        if let Some(policy_file) = cli.network_policy.as_deref() {
+            let policy = NetworkPolicy::load(policy_file)
+                .map_err(|e| anyhow::anyhow!("Failed to load network policy {policy_file}: {e}"))?;
+            https_data = https_data.with_policy(Arc::new(policy));
+        }
+        if let Some(proxy) = ProxyConfig::from_cli(&cli)? {
+            https_data = https_data.with_proxy(Arc::new(proxy));
+        }
+
+        let dashboard = match cli.dashboard_size {
+            0 => None,
+            size => Some(Arc::new(Dashboard::new(size))),
+        };
+
+        Ok(Self {
+            cli,
+            last_output: Default::default(),
+            https_data,
+            dashboard,
+            local_access: Arc::new(LocalAccess::new()?),
+        })
+    }
+}
+
+/// One request for a handler, from HTTP, a schedule or a replay
+pub(crate) struct FlowEvent {
+    pub flows_user: String,
+    pub handler: String,
+    pub method: Method,
+    pub headers: HeaderMap,
+    pub subpath: String,
+    pub query: EventQuery,
+    pub body: EventBody,
+    pub client: ClientInfo,
+}
+
+/// Run the function of `wp`. With a `capture`, the guest's stdio and outbound
+/// calls are kept there as well.
 async fn run_wasm(
     mut wp: FlowsParams,
+    mut https_data: WasmEdgeTlsReqData,
+    capture: Option<&RunCapture>,
 ) -> Result<ImportObject<FlowsParams>, Box<dyn std::error::Error>> {
     use wasmedge_sdk::AsInstance;
     let config = ConfigBuilder::new(CommonConfigOptions::default()).build()?;
@@ -48,8 +166,26 @@ async fn run_wasm(
     let wasm_env = wp.wasm_env.take().unwrap_or_default();
     let preopen = wp.preopen.take().unwrap_or_default();
 
+    let (stdout, stderr): (Box<dyn Write + Send>, Box<dyn Write + Send>) = match capture {
+        Some(capture) => {
+            https_data = https_data.with_call_log(capture.outbound.clone());
+            (
+                Box::new(TeeWriter::new(
+                    std::io::stdout(),
+                    capture.stdout.get_write_buf(),
+                    dashboard::STDIO_LIMIT,
+                )),
+                Box::new(TeeWriter::new(
+                    std::io::stderr(),
+                    capture.stderr.get_write_buf(),
+                    dashboard::STDIO_LIMIT,
+                )),
+            )
+        }
+        None => (Box::new(std::io::stdout()), Box::new(std::io::stderr())),
+    };
     let mut vfs = {
-        let stdio_sys = StdioSys::new(std::io::empty(), std::io::stdout(), std::io::stderr());
+        let stdio_sys = StdioSys::new(std::io::empty(), stdout, stderr);
         VFS::new_with_stdio(stdio_sys)
     };
 
@@ -73,15 +209,25 @@ async fn run_wasm(
     let mut async_wasi =
         wasmedge_sdk::r#async::wasi::AsyncWasiModule::create_from_wasi_context(wasi_ctx)?;
     let mut flow_env = host_func::create_flows_import(wp)?;
-    let mut https_req = tls_wrap_plugin::create_tls_wrap_import(Default::default())?;
-    let mut rustls_plugin = PluginManager::create_plugin_instance("rustls", "rustls_client")?;
+    let mut rustls_plugin = match https_data.rustls_plugin_allowed() {
+        true => Some(PluginManager::create_plugin_instance(
+            "rustls",
+            "rustls_client",
+        )?),
+        false => None,
+    };
+    let mut http_req = http_wrap_plugin::create_http_wrap_import(https_data.clone())?;
+    let mut https_req = tls_wrap_plugin::create_tls_wrap_import(https_data)?;
 
     let mut instance_map: HashMap<String, &mut (dyn AsyncInst + Send)> = HashMap::new();
 
     instance_map.insert(async_wasi.name().to_string(), async_wasi.as_mut());
     instance_map.insert(flow_env.name().unwrap(), &mut flow_env);
     instance_map.insert(https_req.name().unwrap(), &mut https_req);
-    instance_map.insert(rustls_plugin.name().unwrap(), &mut rustls_plugin);
+    instance_map.insert(http_req.name().unwrap(), &mut http_req);
+    if let Some(rustls_plugin) = rustls_plugin.as_mut() {
+        instance_map.insert(rustls_plugin.name().unwrap(), rustls_plugin);
+    }
 
     let store = Store::new(Some(&config), instance_map)?;
 
@@ -122,61 +268,234 @@ fn module_fn(wasm_file: String, fn_name: &str) -> (Result<Module, String>, bool)
 }
 
 async fn handler(
-    State(cli): State<Cli>,
-    method: Method,
-    headers: HeaderMap,
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     Path((user, handler)): Path<(String, String)>,
-    Query(qry): Query<HashMap<String, Value>>,
-    bytes: Bytes,
-) -> impl IntoResponse {
-    handler_inner(
-        cli,
+    Query(pairs): Query<Vec<(String, String)>>,
+    RawQuery(raw_query): RawQuery,
+    request: Request<Body>,
+) -> Response {
+    serve(
+        state,
+        peer,
         user,
         handler,
-        method,
-        headers,
         String::from("/"),
-        qry,
-        bytes,
+        EventQuery::new(raw_query, pairs),
+        request,
     )
     .await
 }
 async fn handler_with_subpath(
-    State(cli): State<Cli>,
-    method: Method,
-    headers: HeaderMap,
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     Path((user, handler, subpath)): Path<(String, String, String)>,
-    Query(qry): Query<HashMap<String, Value>>,
-    bytes: Bytes,
-) -> impl IntoResponse {
-    handler_inner(
-        cli,
+    Query(pairs): Query<Vec<(String, String)>>,
+    RawQuery(raw_query): RawQuery,
+    request: Request<Body>,
+) -> Response {
+    serve(
+        state,
+        peer,
         user,
         handler,
-        method,
-        headers,
         format!("{}", subpath),
-        qry,
-        bytes,
+        EventQuery::new(raw_query, pairs),
+        request,
     )
     .await
 }
 
-async fn handler_inner(
-    cli: Cli,
+/// Run the handler for an HTTP request. Once the guest appends its first
+/// response chunk, the response is streamed to the client while the run goes on.
+async fn serve(
+    state: AppState,
+    peer: SocketAddr,
     flows_user: String,
     handler: String,
-    method: Method,
-    headers: HeaderMap,
     subpath: String,
-    qry: HashMap<String, Value>,
-    bytes: Bytes,
+    query: EventQuery,
+    request: Request<Body>,
+) -> Response {
+    // `/_flowsnet/...` is kept for the executor's own endpoints
+    if flows_user == RESERVED_PREFIX {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let (parts, body) = request.into_parts();
+    let (method, headers) = (parts.method, parts.headers);
+    let body = match read_body(&state, &headers, body).await {
+        Ok(body) => body,
+        Err(status) => return status.into_response(),
+    };
+
+    let client = ClientInfo::new(Some(peer), &headers, &state.cli.trusted_proxy);
+    let (tx, mut rx) = mpsc::channel(RESPONSE_CHUNK_BUFFER);
+    let event = FlowEvent {
+        flows_user,
+        handler,
+        method,
+        headers,
+        subpath,
+        query,
+        body,
+        client,
+    };
+    let run = tokio::spawn(handler_inner(state, event, Some(tx)));
+
+    match rx.recv().await {
+        Some(ResponseChunk::Head { status, headers }) => {
+            // dropped headers are reported by `invoke` once the run is done
+            let (status, headers, _) = response_head(status, headers.as_deref(), StatusCode::OK);
+            let (mut sender, body) = Body::channel();
+            tokio::spawn(async move {
+                while let Some(ResponseChunk::Data(chunk)) = rx.recv().await {
+                    if sender.send_data(chunk.into()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            (status, headers, body).into_response()
+        }
+        // the run finished without streaming
+        _ => match run.await {
+            Ok(res) => res.into_response(),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        },
+    }
+}
+
+async fn read_body(
+    state: &AppState,
+    headers: &HeaderMap,
+    stream: Body,
+) -> Result<EventBody, StatusCode> {
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    EventBody::from_stream(stream, content_length, state.cli.body_limit).await
+}
+
+pub(crate) async fn handler_inner(
+    state: AppState,
+    flow_event: FlowEvent,
+    response_stream: Option<Sender<ResponseChunk>>,
 ) -> (StatusCode, HeaderMap, Vec<u8>) {
-    let bytes = Arc::new(bytes);
+    let started = Instant::now();
+    let body_len = flow_event.body.len();
+    let event = match state.cli.record_dir.is_some() || state.dashboard.is_some() {
+        true => {
+            // the dashboard alone doesn't keep larger bodies, no need to read them
+            let keep_body =
+                state.cli.record_dir.is_some() || body_len <= dashboard::BODY_LIMIT as u64;
+            let bytes = match keep_body {
+                true => flow_event.body.to_bytes().await.map_err(|e| {
+                    tracing::warn!(
+                        "Failed to read the request body, the event is not recorded: {e}"
+                    )
+                }),
+                false => Ok(Bytes::new()),
+            };
+            bytes.ok().map(|bytes| {
+                RecordedEvent::new(
+                    &flow_event.flows_user,
+                    &flow_event.handler,
+                    &flow_event.method,
+                    &flow_event.headers,
+                    &flow_event.subpath,
+                    &flow_event.query,
+                    &bytes,
+                )
+            })
+        }
+        false => None,
+    };
+    let mut record = InvocationRecord::new(
+        &state.cli.flow,
+        &flow_event.flows_user,
+        &flow_event.handler,
+        flow_event.method.as_str(),
+        &flow_event.subpath,
+    );
+    record.request_id = flow_event.client.request_id.clone();
+    let mut capture = state.dashboard.as_ref().map(|_| RunCapture::default());
+
+    let res = invoke(
+        &state,
+        flow_event,
+        response_stream,
+        &mut record,
+        capture.as_mut(),
+    )
+    .await;
+
+    // `response_size` already counts what has been streamed
+    record.finish(res.0, record.response_size + res.2.len(), started.elapsed());
+    // names from the path that matched no function would grow the series without bound
+    let handler_label = match record.handler_found {
+        true => record.handler.as_str(),
+        false => "unknown",
+    };
+    let labels = [handler_label, res.0.as_str()];
+    METRICS.requests.with_label_values(&labels).inc();
+    METRICS
+        .request_duration
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    record.emit();
+
+    let event = event.map(|mut event| {
+        event.handler_fn = record.handler.clone();
+        event.response = Some(RecordedResponse::new(res.0, &res.1, &res.2));
+        event
+    });
+
+    if let (Some(dashboard), Some(event), Some(capture)) =
+        (state.dashboard.as_ref(), event.as_ref(), capture)
+    {
+        dashboard.push(DashboardEntry::new(&record, event, body_len, capture));
+    }
+
+    if let (Some(record_dir), Some(event)) = (state.cli.record_dir.as_deref(), event) {
+        match event.save(record_dir).await {
+            Ok(path) => tracing::debug!("event recorded to {}", path.display()),
+            Err(e) => tracing::warn!("Failed to record event {}: {e}", event.id),
+        }
+    }
 
+    res
+}
+
+async fn invoke(
+    state: &AppState,
+    event: FlowEvent,
+    response_stream: Option<Sender<ResponseChunk>>,
+    record: &mut InvocationRecord,
+    capture: Option<&mut RunCapture>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let cli = &state.cli;
+    let FlowEvent {
+        flows_user,
+        handler,
+        method,
+        headers,
+        subpath,
+        query,
+        body,
+        client,
+    } = event;
+
+    // every header, with the value in base64 so opaque bytes survive
+    let raw_headers = headers
+        .iter()
+        .map(|(key, value)| (key.as_str(), STANDARD.encode(value.as_bytes())))
+        .collect::<Vec<_>>();
+    let mut dropped = vec![];
     let headers = headers.iter().fold(vec![], |mut acc, (key, value)| {
-        if let Ok(v) = value.to_str() {
-            acc.push((key.as_str(), v));
+        match value.to_str() {
+            Ok(v) => acc.push((key.as_str(), v)),
+            Err(_) => dropped.push(key.as_str()),
         }
         acc
     });
@@ -187,7 +506,7 @@ async fn handler_inner(
         match fn_exist {
             true => (handler_fn, wasm_module),
             false => {
-                let (wasm_module, fn_exist) = module_fn(cli.wasm, handler.as_str());
+                let (wasm_module, fn_exist) = module_fn(cli.wasm.clone(), handler.as_str());
                 match fn_exist {
                     true => (handler.clone(), wasm_module),
                     false => {
@@ -197,66 +516,92 @@ async fn handler_inner(
             }
         }
     };
-    let flow_id = cli.flow.clone();
-
-    let work_dir = PathBuf::from(cli.work_dir);
-    let mut env_path = work_dir.clone();
-    env_path.push(cli.env_file);
+    record.handler = handler_fn.clone();
+    record.handler_found = true;
+
+    if !dropped.is_empty() {
+        tracing::warn!(
+            target: "flowsnet::flow",
+            handler = %record.handler,
+            "headers {} are not visible text and left out of `get_event_headers`, \
+             read them with `get_event_headers_raw`",
+            dropped.join(", ")
+        );
+    }
 
     let wp = FlowsParams {
-        listening: 0,
-        flows_user,
-        wasm_module: wasm_module.unwrap(),
-        wasm_env: load_env(env_path),
-        preopen: Some(vec![("/".into(), work_dir)]),
-        flow_id,
         event_method: method.as_str().to_string(),
-        event_query: serde_json::to_string(&qry).unwrap(),
+        event_query: serde_json::to_string(&query.map()).unwrap(),
+        event_query_raw: query.raw.clone(),
+        event_query_multi: serde_json::to_string(&query.multi_map()).unwrap(),
         event_headers: serde_json::to_string(&headers).unwrap(),
+        event_headers_raw: serde_json::to_string(&raw_headers).unwrap(),
         event_subpath: subpath,
-        event_body: bytes.clone(),
-        wasm_func: handler_fn,
-
-        flows: None,
-        error_log: None,
-        output: LinkedList::new(),
-        response: None,
-        response_headers: None,
-        response_status: 0,
-        error_code: 0,
+        event_body: Arc::new(body),
+        event_client: client,
+        response_stream,
+        ..flows_params(cli, flows_user, wasm_module.unwrap(), handler_fn)
     };
 
-    match run_wasm(wp).await {
+    let run_started = Instant::now();
+    let result = run_wasm(wp, state.https_data.clone(), capture.as_deref()).await;
+    METRICS
+        .wasm_execution
+        .with_label_values(&[record.handler.as_str()])
+        .observe(run_started.elapsed().as_secs_f64());
+    if let Some(capture) = capture {
+        capture.wasm_ms = run_started.elapsed().as_millis() as u64;
+    }
+
+    match result {
         Ok(mut wp) => {
             let wp = wp.get_host_data_mut();
 
-            let mut res_status = StatusCode::NO_CONTENT.as_u16();
-            if wp.response_status > 0 {
-                res_status = wp.response_status;
+            let output = FlowOutput::new(&record.handler, &wp.output, wp.flows.as_deref());
+            if let Ok(mut last_output) = state.last_output.write() {
+                *last_output = Some(output);
             }
 
-            let response = wp.response.take().unwrap_or_default();
-            let response_headers = wp.response_headers.take().unwrap_or_default();
-            let res_headers = serde_json::from_slice::<Vec<(String, String)>>(&response_headers)
-                .unwrap_or_default();
-
-            let mut h = HeaderMap::new();
-            for rh in res_headers.into_iter() {
-                if let Ok(hn) = HeaderName::from_bytes(rh.0.as_bytes()) {
-                    if let Ok(hv) = HeaderValue::from_str(&rh.1) {
-                        h.insert(hn, hv);
-                    }
-                }
+            record.error_code = wp.error_code;
+            if let Some(error_log) = wp.error_log.as_ref() {
+                tracing::error!(
+                    target: "flowsnet::flow",
+                    handler = %record.handler,
+                    error_code = wp.error_code,
+                    "{}",
+                    String::from_utf8_lossy(error_log)
+                );
             }
 
-            return (
-                StatusCode::from_u16(res_status).unwrap_or(StatusCode::NO_CONTENT),
-                h,
-                response,
-            );
+            let default = match wp.streaming {
+                true => StatusCode::OK,
+                false => StatusCode::NO_CONTENT,
+            };
+            let (status, h, invalid) =
+                response_head(wp.response_status, wp.response_headers.as_deref(), default);
+            let error_log = report_invalid_headers(record, invalid, wp.error_log.as_deref());
+
+            if wp.streaming {
+                // the client already has the status and headers, an error code
+                // can only be logged by now
+                record.response_size = wp.streamed_len;
+                return (status, h, Vec::new());
+            }
+
+            if wp.error_code != 0 {
+                return flow_error_response(
+                    wp.error_code,
+                    wp.response_status,
+                    error_log.as_deref(),
+                );
+            }
+
+            let response = wp.response.take().unwrap_or_default();
+            return (status, h, response);
         }
         Err(e) => {
             eprintln!("{e}");
+            record.set_error_log(e.to_string().as_bytes());
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 HeaderMap::new(),
@@ -266,17 +611,275 @@ async fn handler_inner(
     }
 }
 
+/// Status and headers of the response as set by the guest, `default` when it
+/// set no status.
+fn response_head(
+    status: u16,
+    headers: Option<&[u8]>,
+    default: StatusCode,
+) -> (StatusCode, HeaderMap, Vec<String>) {
+    let mut invalid = vec![];
+    let res_headers = match headers {
+        Some(headers) => {
+            serde_json::from_slice::<Vec<(String, String)>>(headers).unwrap_or_else(|e| {
+                invalid.push(format!(
+                    "response headers are not a JSON list of pairs: {e}"
+                ));
+                vec![]
+            })
+        }
+        None => vec![],
+    };
+
+    // `append`, so repeated headers like `Set-Cookie` are all kept
+    let mut h = HeaderMap::new();
+    for (name, value) in res_headers.into_iter() {
+        let hn = match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(hn) => hn,
+            Err(_) => {
+                invalid.push(format!("invalid header name `{name}`"));
+                continue;
+            }
+        };
+        match HeaderValue::from_str(&value) {
+            Ok(hv) => {
+                h.append(hn, hv);
+            }
+            Err(_) => invalid.push(format!("invalid value of header `{name}`")),
+        }
+    }
+
+    let status = match status {
+        0 => default,
+        status => StatusCode::from_u16(status).unwrap_or_else(|_| {
+            invalid.push(format!("invalid response status {status}"));
+            default
+        }),
+    };
+    (status, h, invalid)
+}
+
+/// Surface what `response_head` had to drop as warnings and in the invocation
+/// record, and append it to the guest's error log. Returns the combined error
+/// log, which also goes into the JSON body of a flow error response.
+fn report_invalid_headers(
+    record: &mut InvocationRecord,
+    invalid: Vec<String>,
+    error_log: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    let mut log = error_log.map(<[u8]>::to_vec);
+    for problem in invalid.iter() {
+        tracing::warn!(target: "flowsnet::flow", handler = %record.handler, "{problem}");
+        let log = log.get_or_insert_with(Vec::new);
+        if !log.is_empty() && !log.ends_with(b"\n") {
+            log.push(b'\n');
+        }
+        log.extend_from_slice(problem.as_bytes());
+    }
+    if let Some(log) = log.as_ref() {
+        record.set_error_log(log);
+    }
+    record.invalid_headers = invalid;
+    log
+}
+
+/// Parameters for running `wasm_func` with an empty event; callers fill in the event fields.
+fn flows_params(
+    cli: &Cli,
+    flows_user: String,
+    wasm_module: Module,
+    wasm_func: String,
+) -> FlowsParams {
+    let work_dir = PathBuf::from(&cli.work_dir);
+    let mut env_path = work_dir.clone();
+    env_path.push(&cli.env_file);
+
+    FlowsParams {
+        listening: 0,
+        flows_user,
+        wasm_module,
+        wasm_env: load_env(env_path),
+        preopen: Some(vec![("/".into(), work_dir)]),
+        flow_id: cli.flow.clone(),
+        event_method: String::new(),
+        event_query: String::new(),
+        event_query_raw: String::new(),
+        event_query_multi: String::new(),
+        event_headers: String::new(),
+        event_headers_raw: String::new(),
+        event_subpath: String::new(),
+        event_body: Default::default(),
+        event_body_reader: None,
+        event_client: Default::default(),
+        response_stream: None,
+        streaming: false,
+        streamed_len: 0,
+        wasm_func,
+
+        flows: None,
+        error_log: None,
+        output: LinkedList::new(),
+        response: None,
+        response_headers: None,
+        response_status: 0,
+        error_code: 0,
+    }
+}
+
+/// Run `func` with `listening = 1` so the guest registers its triggers through
+/// `set_flows`, the way the platform does on deploy.
+async fn register(state: &AppState, func: &str) -> anyhow::Result<Option<Value>> {
+    let cli = &state.cli;
+    let (wasm_module, fn_exist) = module_fn(cli.wasm.clone(), func);
+    let wasm_module = wasm_module.map_err(anyhow::Error::msg)?;
+    if !fn_exist {
+        anyhow::bail!("function `{func}` is not exported by {}", cli.wasm);
+    }
+
+    let wp = FlowsParams {
+        listening: 1,
+        ..flows_params(cli, String::new(), wasm_module, func.to_string())
+    };
+
+    let mut wp = run_wasm(wp, state.https_data.clone(), None)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    let wp = wp.get_host_data_mut();
+
+    if let Some(error_log) = wp.error_log.as_ref() {
+        tracing::error!(
+            target: "flowsnet::flow",
+            handler = %func,
+            error_code = wp.error_code,
+            "{}",
+            String::from_utf8_lossy(error_log)
+        );
+    }
+
+    let output = FlowOutput::new(func, &wp.output, wp.flows.as_deref());
+    let flows = output.flows.clone();
+    if let Ok(mut last_output) = state.last_output.write() {
+        *last_output = Some(output);
+    }
+
+    Ok(flows)
+}
+
+/// Map an error reported by the flow through `set_error_code` into the response,
+/// the same way the platform does: a 5xx status, the code in the
+/// `X-Flows-Error-Code` header and the error log in a JSON body.
+fn flow_error_response(
+    error_code: u16,
+    response_status: u16,
+    error_log: Option<&[u8]>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let status = StatusCode::from_u16(response_status)
+        .ok()
+        .filter(|s| s.is_server_error())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+    let mut h = HeaderMap::new();
+    h.insert(
+        HeaderName::from_static(FLOWS_ERROR_CODE_HEADER),
+        HeaderValue::from(error_code),
+    );
+    h.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    let body = serde_json::json!({
+        "error_code": error_code,
+        "error_log": error_log.map(String::from_utf8_lossy),
+    });
+
+    (status, h, serde_json::to_vec(&body).unwrap_or_default())
+}
+
+fn load_plugins() {
+    static LOAD: Once = Once::new();
+    LOAD.call_once(|| {
+        _ = PluginManager::load(None);
+    });
+}
+
+/// Re-run an event recorded with `--record-dir` and diff the responses.
+pub async fn replay(mut args: Cli, event_file: String) -> anyhow::Result<()> {
+    load_plugins();
+    // the replayed event is not a new one
+    args.record_dir = None;
+    event_store::replay(AppState::new(args)?, event_file).await
+}
+
 pub async fn start(args: Cli, mut shutdown_rx: broadcast::Receiver<bool>) {
-    _ = PluginManager::load(None);
+    load_plugins();
 
     let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
+    let state = match AppState::new(args) {
+        Ok(state) => state,
+        Err(e) => {
+            tracing::error!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let wasm = match get_module(state.cli.wasm.clone()) {
+        Ok(_) => WasmStatus::Loaded,
+        Err(e) => {
+            tracing::error!("Failed to load {}: {e}", state.cli.wasm);
+            WasmStatus::Failed(e.to_string())
+        }
+    };
+    health::update(|health| health.wasm = wasm);
+
+    let token = state.local_access.token();
+    tracing::info!(
+        "Output of the last run on http://localhost:{}/_flowsnet/debug/output?token={token}",
+        state.cli.port
+    );
+    if state.dashboard.is_some() {
+        tracing::info!(
+            "Dashboard on http://localhost:{}/_flowsnet/ui?token={token}",
+            state.cli.port
+        );
+    }
+
     let app = Router::new()
+        .route("/_flowsnet/debug/output", get(debug::last_output))
+        .route("/_flowsnet/healthz", get(debug::healthz))
+        .route("/_flowsnet/readyz", get(debug::readyz))
+        .route("/_flowsnet/metrics", get(debug::metrics))
+        .route("/_flowsnet/ui", get(dashboard::page))
+        .route("/_flowsnet/ui/invocations", get(dashboard::list))
+        .route("/_flowsnet/ui/invocations/:id", get(dashboard::detail))
+        .route(
+            "/_flowsnet/ui/invocations/:id/replay",
+            post(dashboard::replay),
+        )
         .route("/:user/:handler", any(handler))
         .route("/:user/:handler/*subpath", any(handler_with_subpath))
-        .layer(DefaultBodyLimit::max(10 * 1024 * 1024))
-        .with_state(args);
+        .with_state(state.clone());
+
+    if let Some(func) = state.cli.register.as_deref() {
+        match register(&state, func).await {
+            Ok(Some(_)) => {}
+            Ok(None) => tracing::warn!("`{func}` finished without registering any flows"),
+            Err(e) => tracing::error!("Failed to register flows with `{func}`: {e}"),
+        }
+    }
+
+    if let Some(schedule_file) = state.cli.schedule.as_deref() {
+        match schedule::load(schedule_file) {
+            Ok(triggers) => {
+                for trigger in triggers {
+                    tokio::spawn(schedule::run(state.clone(), trigger));
+                }
+            }
+            Err(e) => tracing::error!("Failed to load schedule file {schedule_file}: {e}"),
+        }
+    }
 
-    let server = Server::bind(&addr).serve(app.into_make_service());
+    let server = Server::bind(&addr).serve(app.into_make_service_with_connect_info::<SocketAddr>());
 
     let graceful = server.with_graceful_shutdown(async {
         shutdown_rx.recv().await.ok();
@@ -7,6 +7,8 @@ use std::{
 use slab::Slab;
 use wasmedge_sdk::r#async::wasi::async_wasi::snapshots::env::{vfs::*, wasi_types, Errno};
 
+use crate::metrics::METRICS;
+
 fn get_file_ino(metadata: &std::fs::Metadata) -> u64 {
     #[cfg(unix)]
     {
@@ -369,7 +371,9 @@ impl WasiFile for DiskFile {
 
     fn fd_read(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> Result<usize, Errno> {
         self.right.can(WASIRights::FD_READ)?;
-        Ok(self.fd.read_vectored(bufs)?)
+        let n = self.fd.read_vectored(bufs)?;
+        METRICS.vfs_read_bytes.inc_by(n as u64);
+        Ok(n)
     }
 
     fn fd_pread(
@@ -385,7 +389,9 @@ impl WasiFile for DiskFile {
         self.fd.seek(SeekFrom::Start(offset))?;
         let r = self.fd.read_vectored(bufs);
         self.fd.seek(SeekFrom::Start(old_seek))?;
-        Ok(r?)
+        let n = r?;
+        METRICS.vfs_read_bytes.inc_by(n as u64);
+        Ok(n)
     }
 
     fn fd_write(&mut self, bufs: &[std::io::IoSlice<'_>]) -> Result<usize, Errno> {
@@ -398,7 +404,9 @@ impl WasiFile for DiskFile {
         if self.fd.metadata()?.len() + buf_len as u64 >= FILE_WRITE_LIMIT {
             return Err(Errno::__WASI_ERRNO_NOSPC);
         }
-        Ok(self.fd.write_vectored(bufs)?)
+        let n = self.fd.write_vectored(bufs)?;
+        METRICS.vfs_written_bytes.inc_by(n as u64);
+        Ok(n)
     }
 
     fn fd_pwrite(
@@ -423,7 +431,9 @@ impl WasiFile for DiskFile {
         self.fd.seek(SeekFrom::Start(offset))?;
         let r = self.fd.write_vectored(bufs);
         self.fd.seek(SeekFrom::Start(old_seek))?;
-        Ok(r?)
+        let n = r?;
+        METRICS.vfs_written_bytes.inc_by(n as u64);
+        Ok(n)
     }
 
     fn fd_seek(
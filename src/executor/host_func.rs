@@ -1,29 +1,52 @@
-use axum::body::Bytes;
-use std::{collections::LinkedList, path::PathBuf, sync::Arc};
+use std::{collections::LinkedList, io::Read, path::PathBuf, sync::Arc};
+use tokio::sync::mpsc::{error::TrySendError, Sender};
 use wasmedge_sdk::{
     error::{CoreError, CoreExecutionError},
     CallingFrame, ImportObject, ImportObjectBuilder, Instance, Module, ValType, WasmEdgeResult,
     WasmValue,
 };
 
+use crate::executor::client_info::ClientInfo;
+use crate::executor::event_body::{EventBody, EventBodyReader};
+
+/// Most bytes `read_event_body_chunk` copies in one call
+const MAX_BODY_CHUNK: usize = 1024 * 1024;
+
 pub fn create_flows_import(flow_params: FlowsParams) -> WasmEdgeResult<ImportObject<FlowsParams>> {
     let mut builder = ImportObjectBuilder::new("env", flow_params)?;
     builder.with_func::<i32, i32>("get_flows_user", get_flows_user)?;
     builder.with_func::<i32, i32>("get_flow_id", get_flow_id)?;
     builder.with_func::<(), i32>("get_event_body_length", get_event_body_length)?;
     builder.with_func::<i32, i32>("get_event_body", get_event_body)?;
+    builder.with_func::<(), i32>("open_event_body", open_event_body)?;
+    builder.with_func::<(i32, i32), i32>("read_event_body_chunk", read_event_body_chunk)?;
     builder.with_func::<(), i32>("get_event_headers_length", get_event_headers_length)?;
     builder.with_func::<i32, i32>("get_event_headers", get_event_headers)?;
+    builder.with_func::<(), i32>("get_event_headers_raw_length", get_event_headers_raw_length)?;
+    builder.with_func::<i32, i32>("get_event_headers_raw", get_event_headers_raw)?;
     builder.with_func::<(), i32>("get_event_query_length", get_event_query_length)?;
     builder.with_func::<i32, i32>("get_event_query", get_event_query)?;
+    builder.with_func::<(), i32>("get_event_query_raw_length", get_event_query_raw_length)?;
+    builder.with_func::<i32, i32>("get_event_query_raw", get_event_query_raw)?;
+    builder.with_func::<(), i32>("get_event_query_multi_length", get_event_query_multi_length)?;
+    builder.with_func::<i32, i32>("get_event_query_multi", get_event_query_multi)?;
     builder.with_func::<(), i32>("get_event_subpath_length", get_event_subpath_length)?;
     builder.with_func::<i32, i32>("get_event_subpath", get_event_subpath)?;
     builder.with_func::<(), i32>("get_event_method_length", get_event_method_length)?;
     builder.with_func::<i32, i32>("get_event_method", get_event_method)?;
+    builder.with_func::<(), i32>("get_event_remote_addr_length", get_event_remote_addr_length)?;
+    builder.with_func::<i32, i32>("get_event_remote_addr", get_event_remote_addr)?;
+    builder.with_func::<(), i32>("get_event_host_length", get_event_host_length)?;
+    builder.with_func::<i32, i32>("get_event_host", get_event_host)?;
+    builder.with_func::<(), i32>("get_event_scheme_length", get_event_scheme_length)?;
+    builder.with_func::<i32, i32>("get_event_scheme", get_event_scheme)?;
+    builder.with_func::<(), i32>("get_event_request_id_length", get_event_request_id_length)?;
+    builder.with_func::<i32, i32>("get_event_request_id", get_event_request_id)?;
     builder.with_func::<(i32, i32), ()>("set_flows", set_flows)?;
     builder.with_func::<(i32, i32), ()>("set_error_log", set_error_log)?;
     builder.with_func::<(i32, i32), ()>("set_output", set_output)?;
     builder.with_func::<(i32, i32), ()>("set_response", set_response)?;
+    builder.with_func::<(i32, i32), ()>("append_response_chunk", append_response_chunk)?;
     builder.with_func::<(i32, i32), ()>("set_response_headers", set_response_headers)?;
     builder.with_func::<i32, ()>("set_response_status", set_response_status)?;
     builder.with_func::<i32, ()>("set_error_code", set_error_code)?;
@@ -36,9 +59,17 @@ pub struct FlowsParams {
     pub flow_id: String,
     pub event_method: String,
     pub event_query: String,
+    /// The query string as sent, without the leading `?`
+    pub event_query_raw: String,
+    /// JSON object of every value of every query key
+    pub event_query_multi: String,
     pub event_headers: String,
+    /// JSON list of every header with its value in base64
+    pub event_headers_raw: String,
     pub event_subpath: String,
-    pub event_body: Arc<Bytes>,
+    pub event_body: Arc<EventBody>,
+    pub event_body_reader: Option<EventBodyReader>,
+    pub event_client: ClientInfo,
     pub wasm_module: Module,
     pub wasm_func: String,
     pub wasm_env: Option<Vec<String>>,
@@ -52,6 +83,22 @@ pub struct FlowsParams {
     pub response_headers: Option<Vec<u8>>,
     pub response_status: u16,
     pub error_code: u16,
+    /// Where `append_response_chunk` forwards the response while the run goes on,
+    /// `None` to collect it into `response` instead
+    pub response_stream: Option<Sender<ResponseChunk>>,
+    /// Whether the status and headers have been sent down `response_stream`
+    pub streaming: bool,
+    pub streamed_len: usize,
+}
+
+/// Part of a response streamed by the guest
+pub enum ResponseChunk {
+    /// Status and headers as set when the first chunk is appended
+    Head {
+        status: u16,
+        headers: Option<Vec<u8>>,
+    },
+    Data(Vec<u8>),
 }
 
 pub fn is_listening(
@@ -107,6 +154,15 @@ pub fn get_flow_id(
     ))
 }
 
+/// Reading a spooled body touches the disk, host functions can't await so
+/// step out of the runtime for it
+fn blocking_io<T>(body: &EventBody, io: impl FnOnce() -> T) -> T {
+    match body {
+        EventBody::File(_) => tokio::task::block_in_place(io),
+        EventBody::Memory(_) => io(),
+    }
+}
+
 pub fn get_event_body_length(
     data: &mut FlowsParams,
     _inst: &mut Instance,
@@ -127,9 +183,83 @@ pub fn get_event_body(
             let mut mem = frame
                 .memory_mut(0)
                 .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
-            mem.set_data(data.event_body.as_ref(), ptr.to_i32() as u32)
+            let body = blocking_io(&data.event_body, || data.event_body.read_all())
+                .map_err(|_| CoreError::Execution(CoreExecutionError::HostFuncFailed))?;
+            mem.set_data(body.as_ref(), ptr.to_i32() as u32)
                 .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
-            return Ok(vec![WasmValue::from_i32(data.event_body.len() as i32)]);
+            return Ok(vec![WasmValue::from_i32(body.len() as i32)]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
+/// Start reading the event body from the beginning with `read_event_body_chunk`,
+/// returns `-1` if it can't be read
+pub fn open_event_body(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    match data.event_body.reader() {
+        Ok(reader) => {
+            data.event_body_reader = Some(reader);
+            Ok(vec![WasmValue::from_i32(0)])
+        }
+        Err(e) => {
+            tracing::warn!("Failed to open the event body: {e}");
+            Ok(vec![WasmValue::from_i32(-1)])
+        }
+    }
+}
+
+/// Copy up to `len` bytes of the event body to `ptr`, returns the number of
+/// bytes copied, `0` at the end of the body and `-1` on errors
+pub fn read_event_body_chunk(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some([ptr, len]) = &args.get(0..2) {
+        if ptr.ty() == ValType::I32 && len.ty() == ValType::I32 {
+            let ptr = ptr.to_i32();
+            let len = len.to_i32().max(0) as usize;
+
+            let reader = match data.event_body_reader.take() {
+                Some(reader) => reader,
+                None => match data.event_body.reader() {
+                    Ok(reader) => reader,
+                    Err(e) => {
+                        tracing::warn!("Failed to open the event body: {e}");
+                        return Ok(vec![WasmValue::from_i32(-1)]);
+                    }
+                },
+            };
+            let reader = data.event_body_reader.insert(reader);
+
+            // the guest may ask for far more than there is
+            let len = len
+                .min(MAX_BODY_CHUNK)
+                .min(data.event_body.len().try_into().unwrap_or(usize::MAX));
+            let mut chunk = vec![0; len];
+            let n = match blocking_io(&data.event_body, || reader.read(&mut chunk)) {
+                Ok(n) => n,
+                Err(e) => {
+                    tracing::warn!("Failed to read the event body: {e}");
+                    return Ok(vec![WasmValue::from_i32(-1)]);
+                }
+            };
+
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(&chunk[..n], ptr as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(n as i32)]);
         }
     }
 
@@ -169,6 +299,72 @@ pub fn get_event_query(
     ))
 }
 
+pub fn get_event_query_raw_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(data.event_query_raw.len() as i32)])
+}
+
+pub fn get_event_query_raw(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(data.event_query_raw.as_bytes(), ptr.to_i32() as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(data.event_query_raw.len() as i32)]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
+pub fn get_event_query_multi_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.event_query_multi.len() as i32
+    )])
+}
+
+pub fn get_event_query_multi(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(data.event_query_multi.as_bytes(), ptr.to_i32() as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(
+                data.event_query_multi.len() as i32
+            )]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
 pub fn get_event_subpath_length(
     data: &mut FlowsParams,
     _inst: &mut Instance,
@@ -231,6 +427,149 @@ pub fn get_event_method(
     ))
 }
 
+pub fn get_event_remote_addr_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.event_client.remote_addr.len() as i32,
+    )])
+}
+
+pub fn get_event_remote_addr(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(
+                data.event_client.remote_addr.as_bytes(),
+                ptr.to_i32() as u32,
+            )
+            .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(
+                data.event_client.remote_addr.len() as i32,
+            )]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
+pub fn get_event_host_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.event_client.host.len() as i32
+    )])
+}
+
+pub fn get_event_host(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(data.event_client.host.as_bytes(), ptr.to_i32() as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(
+                data.event_client.host.len() as i32
+            )]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
+pub fn get_event_scheme_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.event_client.scheme.len() as i32
+    )])
+}
+
+pub fn get_event_scheme(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(data.event_client.scheme.as_bytes(), ptr.to_i32() as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(
+                data.event_client.scheme.len() as i32
+            )]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
+pub fn get_event_request_id_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.event_client.request_id.len() as i32,
+    )])
+}
+
+pub fn get_event_request_id(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(data.event_client.request_id.as_bytes(), ptr.to_i32() as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(
+                data.event_client.request_id.len() as i32,
+            )]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
 pub fn get_event_headers_length(
     data: &mut FlowsParams,
     _inst: &mut Instance,
@@ -262,6 +601,41 @@ pub fn get_event_headers(
     ))
 }
 
+pub fn get_event_headers_raw_length(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.event_headers_raw.len() as i32
+    )])
+}
+
+pub fn get_event_headers_raw(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    mut args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some(ptr) = args.pop() {
+        if ptr.ty() == ValType::I32 {
+            let mut mem = frame
+                .memory_mut(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            mem.set_data(data.event_headers_raw.as_bytes(), ptr.to_i32() as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+            return Ok(vec![WasmValue::from_i32(
+                data.event_headers_raw.len() as i32
+            )]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
 pub fn set_flows(
     data: &mut FlowsParams,
     _inst: &mut Instance,
@@ -311,6 +685,12 @@ pub fn set_output(
                 .get_data(ptr as u32, len as u32)
                 .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
 
+            tracing::info!(
+                target: "flowsnet::output",
+                handler = %data.wasm_func,
+                "{}",
+                String::from_utf8_lossy(&chunk)
+            );
             data.output.push_back(chunk);
             return Ok(vec![]);
         }
@@ -379,6 +759,64 @@ pub fn set_response(
     ))
 }
 
+/// Wait while the stream is full, so a slow client holds the guest back instead
+/// of its chunks piling up in memory.
+fn send_chunk(stream: &Sender<ResponseChunk>, chunk: ResponseChunk) {
+    // a closed stream means the client went away, let the run finish anyway
+    if let Err(TrySendError::Full(chunk)) = stream.try_send(chunk) {
+        // host functions can't await, step out of the runtime to block instead
+        _ = tokio::task::block_in_place(|| stream.blocking_send(chunk));
+    }
+}
+
+pub fn append_response_chunk(
+    data: &mut FlowsParams,
+    _inst: &mut Instance,
+    frame: &mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    if let Some([ptr, len]) = &args.get(0..2) {
+        if ptr.ty() == ValType::I32 && len.ty() == ValType::I32 {
+            let ptr = ptr.to_i32();
+            let len = len.to_i32();
+
+            let mem = frame
+                .memory_ref(0)
+                .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+
+            let chunk = mem
+                .get_data(ptr as u32, len as u32)
+                .map_err(|_| CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+
+            data.streamed_len += chunk.len();
+            match data.response_stream.as_ref() {
+                Some(stream) => {
+                    if !data.streaming {
+                        data.streaming = true;
+                        send_chunk(
+                            stream,
+                            ResponseChunk::Head {
+                                status: data.response_status,
+                                headers: data.response_headers.clone(),
+                            },
+                        );
+                    }
+                    send_chunk(stream, ResponseChunk::Data(chunk));
+                }
+                None => data
+                    .response
+                    .get_or_insert_with(Vec::new)
+                    .extend_from_slice(&chunk),
+            }
+            return Ok(vec![]);
+        }
+    }
+
+    Err(CoreError::Execution(
+        wasmedge_sdk::error::CoreExecutionError::FuncTypeMismatch,
+    ))
+}
+
 pub fn set_response_headers(
     data: &mut FlowsParams,
     _inst: &mut Instance,
@@ -0,0 +1,37 @@
+use wasmedge_sdk::{
+    r#async::import::{ImportObject, ImportObjectBuilder},
+    WasmEdgeResult,
+};
+
+use crate::executor::tls_wrap_plugin::{
+    wasmedge_httpsreq_get_err_, wasmedge_httpsreq_get_err_len_, wasmedge_httpsreq_get_rcv_,
+    wasmedge_httpsreq_get_rcv_len_, wasmedge_httpsreq_send_data_, wasmedge_httpsreq_send_data_v2_,
+    WasmEdgeTlsReqData,
+};
+
+/// Same ABI as `wasmedge_httpsreq`, but the request goes over plain TCP,
+/// e.g. to a mock server on `http://localhost:8080`.
+pub fn create_http_wrap_import(
+    http_data: WasmEdgeTlsReqData,
+) -> WasmEdgeResult<ImportObject<WasmEdgeTlsReqData>> {
+    let mut builder = ImportObjectBuilder::new("wasmedge_httpreq", http_data.plain())?;
+    builder.with_func::<(i32, i32, i32, i32, i32), ()>(
+        "wasmedge_httpreq_send_data",
+        wasmedge_httpsreq_send_data_,
+    )?;
+    builder.with_func::<(), i32>(
+        "wasmedge_httpreq_get_rcv_len",
+        wasmedge_httpsreq_get_rcv_len_,
+    )?;
+    builder.with_func::<i32, ()>("wasmedge_httpreq_get_rcv", wasmedge_httpsreq_get_rcv_)?;
+    builder.with_func::<(i32, i32, i32, i32, i32), i32>(
+        "wasmedge_httpreq_send_data_v2",
+        wasmedge_httpsreq_send_data_v2_,
+    )?;
+    builder.with_func::<(), i32>(
+        "wasmedge_httpreq_get_err_len",
+        wasmedge_httpsreq_get_err_len_,
+    )?;
+    builder.with_func::<i32, ()>("wasmedge_httpreq_get_err", wasmedge_httpsreq_get_err_)?;
+    Ok(builder.build())
+}
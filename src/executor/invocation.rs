@@ -0,0 +1,97 @@
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const ERROR_LOG_EXCERPT_LEN: usize = 512;
+
+/// One record per handler run, emitted through `tracing` once the run is done.
+#[derive(Clone, Debug, Serialize)]
+pub struct InvocationRecord {
+    /// Milliseconds since the unix epoch when the request arrived
+    pub timestamp: u64,
+    pub flow_id: String,
+    pub flows_user: String,
+    pub request_id: String,
+    /// Exported wasm function which handled the request, or the handler name
+    /// from the path when none matched
+    pub handler: String,
+    /// Whether `handler` is an exported function
+    #[serde(skip)]
+    pub handler_found: bool,
+    pub method: String,
+    pub subpath: String,
+    pub status: u16,
+    pub response_size: usize,
+    pub duration_ms: u64,
+    pub error_code: u16,
+    pub error_log: Option<String>,
+    /// Response headers or status set by the guest which had to be dropped
+    pub invalid_headers: Vec<String>,
+}
+
+impl InvocationRecord {
+    pub fn new(
+        flow_id: &str,
+        flows_user: &str,
+        handler: &str,
+        method: &str,
+        subpath: &str,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            flow_id: flow_id.to_string(),
+            flows_user: flows_user.to_string(),
+            request_id: String::new(),
+            handler: handler.to_string(),
+            handler_found: false,
+            method: method.to_string(),
+            subpath: subpath.to_string(),
+            status: 0,
+            response_size: 0,
+            duration_ms: 0,
+            error_code: 0,
+            error_log: None,
+            invalid_headers: vec![],
+        }
+    }
+
+    pub fn set_error_log(&mut self, log: &[u8]) {
+        let log = String::from_utf8_lossy(log);
+        let excerpt = match log.char_indices().nth(ERROR_LOG_EXCERPT_LEN) {
+            Some((idx, _)) => format!("{}...", &log[..idx]),
+            None => log.into_owned(),
+        };
+        self.error_log = Some(excerpt);
+    }
+
+    pub fn finish(&mut self, status: StatusCode, response_size: usize, duration: Duration) {
+        self.status = status.as_u16();
+        self.response_size = response_size;
+        self.duration_ms = duration.as_millis() as u64;
+    }
+
+    pub fn emit(&self) {
+        tracing::info!(
+            target: "flowsnet::invocation",
+            timestamp = self.timestamp,
+            flow_id = %self.flow_id,
+            flows_user = %self.flows_user,
+            request_id = %self.request_id,
+            handler = %self.handler,
+            method = %self.method,
+            subpath = %self.subpath,
+            status = self.status,
+            response_size = self.response_size,
+            duration_ms = self.duration_ms,
+            error_code = self.error_code,
+            error_log = self.error_log.as_deref().unwrap_or_default(),
+            invalid_headers = %self.invalid_headers.join("; "),
+            "invocation"
+        );
+    }
+}
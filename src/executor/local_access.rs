@@ -0,0 +1,75 @@
+use axum::http::{HeaderMap, StatusCode};
+use std::net::SocketAddr;
+
+/// Carries the token on API calls, pages take it from `?token=`
+const TOKEN_HEADER: &str = "x-flowsnet-token";
+
+/// Guards the endpoints showing what the guest handled and printed. Anyone
+/// reaching the port through the tunnel connects from loopback as well, so
+/// besides a local peer they need a token, random per process.
+pub struct LocalAccess {
+    token: String,
+}
+
+impl LocalAccess {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut bytes = [0u8; 16];
+        getrandom::getrandom(&mut bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to generate the access token: {e}"))?;
+        let token = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        Ok(Self { token })
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn check(
+        &self,
+        peer: &SocketAddr,
+        token: Option<&str>,
+    ) -> Result<(), (StatusCode, String)> {
+        if !peer.ip().is_loopback() {
+            return Err((StatusCode::NOT_FOUND, String::new()));
+        }
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), self.token.as_bytes()) => Ok(()),
+            _ => Err((
+                StatusCode::FORBIDDEN,
+                String::from("pass the token printed at startup"),
+            )),
+        }
+    }
+}
+
+pub fn header_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(TOKEN_HEADER).and_then(|v| v.to_str().ok())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks() {
+        let access = LocalAccess::new().unwrap();
+        let local = "127.0.0.1:1234".parse().unwrap();
+        let remote = "8.8.8.8:1234".parse().unwrap();
+        let token = access.token().to_string();
+
+        assert!(access.check(&local, Some(&token)).is_ok());
+        assert_eq!(
+            access.check(&remote, Some(&token)).unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            access.check(&local, None).unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+        assert!(access.check(&local, Some("0")).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::executor::raw_http::{self, RequestHead};
+
+/// Hand-written stubs for the guest's outbound HTTPS calls, e.g.
+/// ```toml
+/// unmatched = "reject"
+///
+/// [[rule]]
+/// host = "api.openai.com"
+/// method = "POST"
+/// path = "/v1/chat/*"
+/// status = 429
+/// headers = { content-type = "application/json", retry-after = "1" }
+/// body = '{"error": {"message": "Rate limit reached"}}'
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct MockRules {
+    #[serde(default)]
+    pub unmatched: Unmatched,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<MockRule>,
+}
+
+/// What to do with a request no rule matches
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unmatched {
+    #[default]
+    Passthrough,
+    Reject,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MockRule {
+    /// Exact host name, or `*.example.com` for any of its subdomains
+    pub host: String,
+    pub port: Option<u16>,
+    pub method: Option<String>,
+    /// Exact path, or a prefix when it ends with `*`; the query string is ignored
+    pub path: Option<String>,
+
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl MockRules {
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn find(&self, host: &str, port: u16, request: &[u8]) -> Option<&MockRule> {
+        let head = raw_http::parse_request_head(request);
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(host, port, head.as_ref()))
+    }
+}
+
+impl MockRule {
+    fn matches(&self, host: &str, port: u16, head: Option<&RequestHead>) -> bool {
+        if !host_matches(&self.host, host) || self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+
+        if self.method.is_none() && self.path.is_none() {
+            return true;
+        }
+        let Some(head) = head else {
+            return false;
+        };
+
+        if let Some(method) = self.method.as_deref() {
+            if !method.eq_ignore_ascii_case(&head.method) {
+                return false;
+            }
+        }
+        if let Some(path) = self.path.as_deref() {
+            let matched = match path.strip_suffix('*') {
+                Some(prefix) => head.path_only().starts_with(prefix),
+                None => head.path_only() == path,
+            };
+            if !matched {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn response(&self) -> Vec<u8> {
+        let headers: Vec<(String, String)> = self
+            .headers
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        raw_http::build_response(self.status, &headers, self.body.as_bytes())
+    }
+}
+
+/// Match a host against an exact name or a `*.example.com` pattern.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+        None => pattern == host,
+    }
+}
@@ -1,7 +1,25 @@
+mod base64_bytes;
+mod cassette;
+mod client_info;
+mod connection_pool;
+mod dashboard;
+mod debug;
+mod event_body;
+mod event_query;
+mod event_store;
 mod executor;
 mod flow_file_sys;
 mod host_func;
+mod http_wrap_plugin;
+mod invocation;
+mod local_access;
+mod mock;
+mod network_policy;
+mod outbound_error;
+mod outbound_log;
+mod raw_http;
 mod read_write_buf;
+mod schedule;
 mod tls_wrap_plugin;
 
 pub use executor::*;
@@ -0,0 +1,194 @@
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use crate::executor::mock::host_matches;
+use crate::executor::tls_wrap_plugin::Transport;
+
+/// Which hosts and ports the guest may reach through `wasmedge_httpsreq`, e.g.
+/// ```toml
+/// allow_hosts = ["api.github.com", "*.openai.com"]
+/// allow_ports = [443]
+/// deny_private = true
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct NetworkPolicy {
+    /// Host names or `*.example.com` patterns; empty allows any host
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+    /// Empty allows any port
+    #[serde(default)]
+    pub allow_ports: Vec<u16>,
+    /// Refuse hosts resolving to loopback, private or link-local addresses
+    #[serde(default = "default_deny_private")]
+    pub deny_private: bool,
+    /// Only let `wasmedge_httpreq` reach loopback addresses; the host and port
+    /// allowlists don't apply to it then
+    #[serde(default = "default_plain_http_local_only")]
+    pub plain_http_local_only: bool,
+    /// The `rustls_client` plugin does its own networking which can't be checked
+    /// here, so it is only registered when allowed explicitly
+    #[serde(default)]
+    pub allow_rustls_plugin: bool,
+}
+
+fn default_deny_private() -> bool {
+    true
+}
+
+fn default_plain_http_local_only() -> bool {
+    true
+}
+
+impl NetworkPolicy {
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Check the host and port against the allowlists, before anything is
+    /// resolved. `Err` carries the reason the connection is refused.
+    pub fn check_host(&self, host: &str, port: u16, transport: Transport) -> Result<(), String> {
+        if transport == Transport::Plain && self.plain_http_local_only {
+            // only the addresses matter then, see `check_addr`
+            return Ok(());
+        }
+
+        if !self.allow_hosts.is_empty() && !self.allow_hosts.iter().any(|p| host_matches(p, host)) {
+            return Err(format!("host {host} is not allowed by the network policy"));
+        }
+        if !self.allow_ports.is_empty() && !self.allow_ports.contains(&port) {
+            return Err(format!("port {port} is not allowed by the network policy"));
+        }
+        Ok(())
+    }
+
+    /// Check an address `host` resolved to, right before connecting to exactly
+    /// that address, so the name can't resolve differently in between.
+    pub fn check_addr(
+        &self,
+        host: &str,
+        addr: &SocketAddr,
+        transport: Transport,
+    ) -> Result<(), String> {
+        let ip = addr.ip();
+        if transport == Transport::Plain && self.plain_http_local_only {
+            if !ip.is_loopback() {
+                return Err(format!(
+                    "{host} resolves to {ip}, but plain HTTP is only allowed to local addresses"
+                ));
+            }
+        } else if self.deny_private && is_private(&ip) {
+            return Err(format!(
+                "{host} resolves to the private address {ip}, which the network policy denies"
+            ));
+        }
+        Ok(())
+    }
+
+    /// `check_addr` for every address `host` resolves to here, for connections
+    /// through a proxy, which resolves the host itself.
+    pub async fn check_resolved(
+        &self,
+        host: &str,
+        port: u16,
+        transport: Transport,
+    ) -> Result<(), String> {
+        let addrs = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("failed to resolve {host}: {e}"))?;
+        for addr in addrs {
+            self.check_addr(host, &addr, transport)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(&ip),
+            None => is_private_v6(ip),
+        },
+    }
+}
+
+fn is_private_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // shared address space, 100.64.0.0/10
+        || (a == 100 && (b & 0b1100_0000) == 64)
+}
+
+fn is_private_v6(ip: &Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn private_v4() {
+        for addr in [
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "255.255.255.255",
+            "100.64.0.1",
+            "100.127.255.255",
+        ] {
+            assert!(is_private(&ip(addr)), "{addr}");
+        }
+        for addr in ["8.8.8.8", "172.32.0.1", "100.128.0.1", "100.63.255.255"] {
+            assert!(!is_private(&ip(addr)), "{addr}");
+        }
+    }
+
+    #[test]
+    fn private_v6() {
+        for addr in [
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(is_private(&ip(addr)), "{addr}");
+        }
+        for addr in ["2001:4860:4860::8888", "fec0::1", "::ffff:8.8.8.8"] {
+            assert!(!is_private(&ip(addr)), "{addr}");
+        }
+    }
+
+    #[test]
+    fn addr_checks() {
+        let policy: NetworkPolicy = toml::from_str("").unwrap();
+        let local = "127.0.0.1:8080".parse().unwrap();
+        let public = "8.8.8.8:443".parse().unwrap();
+
+        assert!(policy.check_addr("h", &local, Transport::Tls).is_err());
+        assert!(policy.check_addr("h", &public, Transport::Tls).is_ok());
+        assert!(policy.check_addr("h", &local, Transport::Plain).is_ok());
+        assert!(policy.check_addr("h", &public, Transport::Plain).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+
+use crate::executor::raw_http;
+
+/// Set on responses made up by the host rather than received from the remote
+pub const FLOWSNET_ERROR_HEADER: &str = "x-flowsnet-error";
+
+/// Why an outbound call of the guest did not get a response.
+#[derive(Debug)]
+pub enum OutboundError {
+    /// Refused by the network policy
+    Denied(String),
+    /// Refused by `unmatched = "reject"` mock rules or a replaying cassette
+    NoMatch(String),
+    /// The proxy could not open a tunnel to the remote
+    Proxy(String),
+    /// Not valid UTF-8 or not a valid DNS name
+    InvalidHost(String),
+    /// The host name could not be resolved
+    Resolve(std::io::Error),
+    ConnectionRefused,
+    ConnectTimeout,
+    /// The TLS handshake failed, e.g. on an untrusted certificate
+    Tls(std::io::Error),
+    HandshakeTimeout,
+    ReadTimeout,
+    ResponseTooLarge(usize),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for OutboundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Denied(reason) => write!(f, "{reason}"),
+            Self::NoMatch(reason) => write!(f, "{reason}"),
+            Self::Proxy(reason) => write!(f, "{reason}"),
+            Self::InvalidHost(host) => write!(f, "invalid host name `{host}`"),
+            Self::Resolve(e) => write!(f, "failed to resolve the host: {e}"),
+            Self::ConnectionRefused => write!(f, "the remote refused the connection"),
+            Self::ConnectTimeout => write!(f, "timed out connecting to the remote"),
+            Self::Tls(e) => write!(f, "TLS handshake failed: {e}"),
+            Self::HandshakeTimeout => write!(f, "timed out during the TLS handshake"),
+            Self::ReadTimeout => write!(f, "timed out reading the response"),
+            Self::ResponseTooLarge(max) => write!(f, "response exceeds the limit of {max} bytes"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for OutboundError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl OutboundError {
+    /// Short name carried in the `x-flowsnet-error` header
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Denied(_) => "network-policy",
+            Self::NoMatch(_) => "no-match",
+            Self::Proxy(_) => "proxy",
+            Self::InvalidHost(_) => "invalid-host",
+            Self::Resolve(_) => "dns",
+            Self::ConnectionRefused => "connection-refused",
+            Self::ConnectTimeout => "connect-timeout",
+            Self::Tls(_) => "tls",
+            Self::HandshakeTimeout => "handshake-timeout",
+            Self::ReadTimeout => "read-timeout",
+            Self::ResponseTooLarge(_) => "response-too-large",
+            Self::Io(_) => "io",
+        }
+    }
+
+    /// Status of the response made up for the guest, `None` for errors which
+    /// trap the run instead.
+    fn status(&self) -> Option<u16> {
+        match self {
+            Self::Denied(_) => Some(403),
+            Self::ConnectTimeout | Self::HandshakeTimeout | Self::ReadTimeout => Some(504),
+            Self::Proxy(_) | Self::ResponseTooLarge(_) => Some(502),
+            Self::NoMatch(_)
+            | Self::InvalidHost(_)
+            | Self::Resolve(_)
+            | Self::ConnectionRefused
+            | Self::Tls(_)
+            | Self::Io(_) => None,
+        }
+    }
+
+    /// Status returned to the guest by `wasmedge_httpsreq_send_data_v2`, `0`
+    /// meaning success
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Denied(_) => 1,
+            Self::NoMatch(_) => 2,
+            Self::Proxy(_) => 3,
+            Self::InvalidHost(_) => 4,
+            Self::Resolve(_) => 5,
+            Self::ConnectionRefused => 6,
+            Self::ConnectTimeout => 7,
+            Self::Tls(_) => 8,
+            Self::HandshakeTimeout => 9,
+            Self::ReadTimeout => 10,
+            Self::ResponseTooLarge(_) => 11,
+            Self::Io(_) => 12,
+        }
+    }
+
+    /// Answer the guest with a made up response, so it sees why its call failed
+    /// instead of having the whole run trapped.
+    pub fn to_response(&self) -> Option<Vec<u8>> {
+        let headers = [
+            (String::from("content-type"), String::from("text/plain")),
+            (
+                String::from(FLOWSNET_ERROR_HEADER),
+                String::from(self.kind()),
+            ),
+        ];
+        self.status()
+            .map(|status| raw_http::build_response(status, &headers, self.to_string().as_bytes()))
+    }
+}
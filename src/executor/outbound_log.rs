@@ -0,0 +1,198 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::executor::raw_http;
+
+/// Request headers worth logging for an outbound call
+const LOGGED_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "accept",
+    "user-agent",
+    "authorization",
+    "x-api-key",
+];
+/// Headers whose value is never logged
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "cookie",
+    "set-cookie",
+];
+
+/// Summary of one outbound call made by the guest through `wasmedge_httpsreq`.
+#[derive(Clone, Debug, Serialize)]
+pub struct OutboundCallRecord {
+    pub host: String,
+    pub port: u16,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub status: Option<u16>,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+impl OutboundCallRecord {
+    pub fn new(
+        host: &str,
+        port: u16,
+        request: &[u8],
+        response: Result<&[u8], String>,
+        latency: Duration,
+    ) -> Self {
+        let head = raw_http::parse_request_head(request);
+        let headers = head
+            .as_ref()
+            .map(|head| {
+                head.headers
+                    .iter()
+                    .filter(|(name, _)| {
+                        LOGGED_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+                    })
+                    .map(|(name, value)| (name.clone(), redact(name, value)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let (status, bytes_received, error) = match response {
+            Ok(resp) => (raw_http::parse_response_status(resp), resp.len(), None),
+            Err(e) => (None, 0, Some(e)),
+        };
+
+        Self {
+            host: host.to_string(),
+            port,
+            method: head.as_ref().map(|h| h.method.clone()),
+            path: head.map(|h| redact_query(&h.path)),
+            headers,
+            status,
+            bytes_sent: request.len(),
+            bytes_received,
+            latency_ms: latency.as_millis() as u64,
+            error,
+        }
+    }
+
+    pub fn emit(&self) {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        tracing::info!(
+            target: "flowsnet::outbound",
+            host = %self.host,
+            port = self.port,
+            method = self.method.as_deref().unwrap_or_default(),
+            path = self.path.as_deref().unwrap_or_default(),
+            headers = %headers,
+            status = self.status.unwrap_or_default(),
+            bytes_sent = self.bytes_sent,
+            bytes_received = self.bytes_received,
+            latency_ms = self.latency_ms,
+            error = self.error.as_deref().unwrap_or_default(),
+            "outbound call"
+        );
+    }
+}
+
+pub(crate) fn redact(name: &str, value: &[u8]) -> String {
+    if REDACTED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+        // keep the auth scheme, e.g. `Bearer <redacted>`
+        let value = String::from_utf8_lossy(value);
+        match value.split_once(' ') {
+            Some((scheme, _)) => format!("{scheme} <redacted>"),
+            None => String::from("<redacted>"),
+        }
+    } else {
+        String::from_utf8_lossy(value).into_owned()
+    }
+}
+
+/// `path` with the values of its query replaced, they often carry keys and
+/// tokens, e.g. `/v1/items?key=<redacted>&page=<redacted>`
+pub(crate) fn redact_query(path: &str) -> String {
+    match path.split_once('?') {
+        Some((path, query)) => format!("{path}?{}", redact_query_values(query)),
+        None => path.to_string(),
+    }
+}
+
+/// A query string like `key=abc&page=2` as `key=<redacted>&page=<redacted>`
+pub(crate) fn redact_query_values(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) => format!("{name}=<redacted>"),
+            None => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Writes the raw bytes of every outbound request/response pair into a directory.
+#[derive(Debug)]
+pub struct OutboundDump {
+    dir: PathBuf,
+    seq: AtomicU64,
+}
+
+impl OutboundDump {
+    pub fn new<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            seq: AtomicU64::new(0),
+        })
+    }
+
+    pub async fn write(&self, host: &str, port: u16, request: &[u8], response: &[u8]) {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        // the host comes from the guest, keep it from leaving the directory
+        let host = host
+            .chars()
+            .map(
+                |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    true => c,
+                    false => '_',
+                },
+            )
+            .collect::<String>();
+        let prefix = format!("{seq:06}-{host}-{port}");
+
+        for (suffix, data) in [("request", request), ("response", response)] {
+            let path = self.dir.join(format!("{prefix}.{suffix}"));
+            if let Err(e) = tokio::fs::write(&path, data).await {
+                tracing::warn!(
+                    target: "flowsnet::outbound",
+                    "Failed to dump {}: {e}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_values_redacted() {
+        assert_eq!(redact_query("/v1/items"), "/v1/items");
+        assert_eq!(
+            redact_query("/v1/items?key=abc&page=2&flag"),
+            "/v1/items?key=<redacted>&page=<redacted>&flag"
+        );
+        assert_eq!(redact_query("/?"), "/?");
+    }
+}
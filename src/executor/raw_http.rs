@@ -0,0 +1,308 @@
+//! Helpers for looking into the raw HTTP/1.x bytes the guest sends through
+//! `wasmedge_httpsreq` and the raw bytes it gets back.
+
+const MAX_HEADERS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct RequestHead {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+impl RequestHead {
+    /// Path without the query string
+    pub fn path_only(&self) -> &str {
+        self.path.split('?').next().unwrap_or_default()
+    }
+}
+
+/// Parse the request line and headers, `None` if the bytes are not an HTTP request.
+pub fn parse_request_head(raw: &[u8]) -> Option<RequestHead> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut req = httparse::Request::new(&mut headers);
+    req.parse(raw).ok()?;
+
+    Some(RequestHead {
+        method: req.method?.to_string(),
+        path: req.path?.to_string(),
+        headers: req
+            .headers
+            .iter()
+            .map(|h| (h.name.to_string(), h.value.to_vec()))
+            .collect(),
+    })
+}
+
+/// Status code from the response's status line
+pub fn parse_response_status(raw: &[u8]) -> Option<u16> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut resp = httparse::Response::new(&mut headers);
+    // the status line is kept even if the headers fail to parse
+    _ = resp.parse(raw);
+    resp.code
+}
+
+/// How the end of a response body is found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyLength {
+    Empty,
+    Fixed(usize),
+    Chunked,
+    /// The remote closes the connection after the body
+    UntilClose,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseHead {
+    /// Bytes up to and including the blank line after the headers
+    pub len: usize,
+    pub body: BodyLength,
+    /// Whether the connection may carry another request afterwards
+    pub keep_alive: bool,
+}
+
+/// Parse the head of the final response in `raw`, skipping interim `1xx` ones.
+/// `Ok(None)` means more bytes are needed.
+pub fn parse_response_head(raw: &[u8], head_request: bool) -> Result<Option<ResponseHead>, String> {
+    let mut offset = 0;
+    loop {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut resp = httparse::Response::new(&mut headers);
+        let len = match resp.parse(&raw[offset..]).map_err(|e| e.to_string())? {
+            httparse::Status::Complete(len) => len,
+            httparse::Status::Partial => return Ok(None),
+        };
+        let status = resp.code.unwrap_or_default();
+        if (100..200).contains(&status) && status != 101 {
+            offset += len;
+            continue;
+        }
+
+        let header = |name: &str| {
+            resp.headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case(name))
+                .map(|h| String::from_utf8_lossy(h.value).to_ascii_lowercase())
+        };
+        let connection = header("connection").unwrap_or_default();
+        let mut keep_alive = match resp.version {
+            Some(1) => !connection.contains("close"),
+            _ => connection.contains("keep-alive"),
+        };
+
+        let body = if head_request || status == 204 || status == 304 {
+            BodyLength::Empty
+        } else if header("transfer-encoding").is_some_and(|te| te.contains("chunked")) {
+            BodyLength::Chunked
+        } else if let Some(content_length) = header("content-length") {
+            let content_length = content_length
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid content-length `{content_length}`"))?;
+            BodyLength::Fixed(content_length)
+        } else {
+            keep_alive = false;
+            BodyLength::UntilClose
+        };
+
+        return Ok(Some(ResponseHead {
+            len: offset + len,
+            body,
+            keep_alive,
+        }));
+    }
+}
+
+/// Length of a complete chunked body at the start of `raw`, including the
+/// last chunk and trailers; `Ok(None)` means more bytes are needed.
+pub fn chunked_body_len(raw: &[u8]) -> Result<Option<usize>, String> {
+    let mut pos = 0;
+    loop {
+        let (len, size) = match httparse::parse_chunk_size(&raw[pos..])
+            .map_err(|_| String::from("invalid chunk size"))?
+        {
+            httparse::Status::Complete(chunk) => chunk,
+            httparse::Status::Partial => return Ok(None),
+        };
+        pos += len;
+
+        if size == 0 {
+            // trailers end with an empty line
+            let mut line_start = pos;
+            loop {
+                let Some(line_len) = raw[line_start..].windows(2).position(|w| w == b"\r\n") else {
+                    return Ok(None);
+                };
+                if line_len == 0 {
+                    return Ok(Some(line_start + 2));
+                }
+                line_start += line_len + 2;
+            }
+        }
+
+        // chunk data and its trailing CRLF; the size comes from the remote
+        pos = usize::try_from(size)
+            .ok()
+            .and_then(|size| pos.checked_add(size)?.checked_add(2))
+            .ok_or_else(|| String::from("chunk size too large"))?;
+        if pos > raw.len() {
+            return Ok(None);
+        }
+    }
+}
+
+/// Whether the request asks for the connection to be closed afterwards
+pub fn wants_close(head: &RequestHead) -> bool {
+    head.headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("connection")
+            && String::from_utf8_lossy(value)
+                .to_ascii_lowercase()
+                .contains("close")
+    })
+}
+
+/// Whether sending the request twice has the same effect as sending it once
+pub fn is_idempotent(method: &str) -> bool {
+    ["GET", "HEAD", "OPTIONS", "TRACE", "PUT", "DELETE"]
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Serialize a complete `HTTP/1.1` response with a `Content-Length` framed body.
+pub fn build_response(status: u16, headers: &[(String, String)], body: &[u8]) -> Vec<u8> {
+    let reason = axum::http::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+
+    let mut resp = format!("HTTP/1.1 {status} {reason}\r\n");
+    for (name, value) in headers.iter() {
+        if name.eq_ignore_ascii_case("content-length") {
+            continue;
+        }
+        resp.push_str(&format!("{name}: {value}\r\n"));
+    }
+    resp.push_str(&format!("content-length: {}\r\n\r\n", body.len()));
+
+    let mut resp = resp.into_bytes();
+    resp.extend_from_slice(body);
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(raw: &str) -> RequestHead {
+        parse_request_head(raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn response_head_framing() {
+        let raw = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello";
+        let head = parse_response_head(raw, false).unwrap().unwrap();
+        assert_eq!(head.len, raw.len() - 5);
+        assert_eq!(head.body, BodyLength::Fixed(5));
+        assert!(head.keep_alive);
+
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let head = parse_response_head(raw, false).unwrap().unwrap();
+        assert_eq!(head.body, BodyLength::Chunked);
+
+        let raw = b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\n\r\n";
+        let head = parse_response_head(raw, false).unwrap().unwrap();
+        assert_eq!(head.body, BodyLength::UntilClose);
+        assert!(!head.keep_alive);
+
+        let raw = b"HTTP/1.1 204 No Content\r\n\r\n";
+        let head = parse_response_head(raw, false).unwrap().unwrap();
+        assert_eq!(head.body, BodyLength::Empty);
+
+        let raw = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n";
+        let head = parse_response_head(raw, true).unwrap().unwrap();
+        assert_eq!(head.body, BodyLength::Empty);
+    }
+
+    #[test]
+    fn response_head_connection() {
+        let raw = b"HTTP/1.1 200 OK\r\nConnection: close\r\ncontent-length: 0\r\n\r\n";
+        assert!(!parse_response_head(raw, false).unwrap().unwrap().keep_alive);
+
+        let raw = b"HTTP/1.0 200 OK\r\ncontent-length: 0\r\n\r\n";
+        assert!(!parse_response_head(raw, false).unwrap().unwrap().keep_alive);
+
+        let raw = b"HTTP/1.0 200 OK\r\nConnection: keep-alive\r\ncontent-length: 0\r\n\r\n";
+        assert!(parse_response_head(raw, false).unwrap().unwrap().keep_alive);
+    }
+
+    #[test]
+    fn response_head_skips_interim() {
+        let interim = "HTTP/1.1 100 Continue\r\n\r\n";
+        let raw = format!("{interim}HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok");
+        let head = parse_response_head(raw.as_bytes(), false).unwrap().unwrap();
+        assert_eq!(head.len, raw.len() - 2);
+        assert_eq!(head.body, BodyLength::Fixed(2));
+
+        assert!(parse_response_head(interim.as_bytes(), false)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn response_head_partial_and_invalid() {
+        assert!(parse_response_head(b"HTTP/1.1 200 OK\r\ncontent-", false)
+            .unwrap()
+            .is_none());
+        assert!(
+            parse_response_head(b"HTTP/1.1 200 OK\r\ncontent-length: x\r\n\r\n", false).is_err()
+        );
+        assert!(parse_response_head(b"not http\r\n\r\n", false).is_err());
+    }
+
+    #[test]
+    fn chunked_body() {
+        let body = b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        assert_eq!(chunked_body_len(body), Ok(Some(body.len())));
+
+        // anything after the body is not counted
+        let mut next = body.to_vec();
+        next.extend_from_slice(b"HTTP/1.1 200 OK\r\n");
+        assert_eq!(chunked_body_len(&next), Ok(Some(body.len())));
+
+        let trailers = b"2\r\nhi\r\n0\r\nx-checksum: 1\r\n\r\n";
+        assert_eq!(chunked_body_len(trailers), Ok(Some(trailers.len())));
+    }
+
+    #[test]
+    fn chunked_body_partial() {
+        assert_eq!(chunked_body_len(b""), Ok(None));
+        assert_eq!(chunked_body_len(b"5\r\nhel"), Ok(None));
+        assert_eq!(chunked_body_len(b"5\r\nhello\r\n0\r\n"), Ok(None));
+    }
+
+    #[test]
+    fn chunked_body_invalid() {
+        assert!(chunked_body_len(b"zz\r\n").is_err());
+        assert!(chunked_body_len(b"ffffffffffffffff\r\n").is_err());
+    }
+
+    #[test]
+    fn idempotent_methods() {
+        assert!(is_idempotent("GET"));
+        assert!(is_idempotent("put"));
+        assert!(!is_idempotent("POST"));
+        assert!(!is_idempotent("PATCH"));
+    }
+
+    #[test]
+    fn close_requested() {
+        assert!(wants_close(&request(
+            "GET / HTTP/1.1\r\nHost: a\r\nConnection: Close\r\n\r\n"
+        )));
+        assert!(!wants_close(&request(
+            "GET / HTTP/1.1\r\nHost: a\r\nConnection: keep-alive\r\n\r\n"
+        )));
+        assert!(!wants_close(&request("GET / HTTP/1.1\r\nHost: a\r\n\r\n")));
+    }
+}
@@ -40,3 +40,38 @@ impl ReadWriteBuf {
         }
     }
 }
+
+impl Default for ReadWriteBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes through to `inner` and keeps a copy of the first `limit` bytes,
+/// e.g. to show the guest's stdout while it still reaches the terminal.
+pub struct TeeWriter<W> {
+    inner: W,
+    copy: WriteBuf,
+    limit: usize,
+}
+
+impl<W: Write> TeeWriter<W> {
+    pub fn new(inner: W, copy: WriteBuf, limit: usize) -> Self {
+        Self { inner, copy, limit }
+    }
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        if let Ok(mut copy) = self.copy.rc_buf.write() {
+            let room = self.limit.saturating_sub(copy.len());
+            copy.extend_from_slice(&buf[..n.min(room)]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,108 @@
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, Method},
+};
+use chrono::Utc;
+use cron::Schedule;
+use serde::Deserialize;
+use std::{path::Path, str::FromStr};
+
+use crate::executor::client_info::ClientInfo;
+use crate::executor::event_query::EventQuery;
+use crate::executor::{handler_inner, AppState, FlowEvent};
+
+#[derive(Deserialize)]
+struct ScheduleFile {
+    #[serde(default)]
+    schedule: Vec<ScheduleEntry>,
+}
+
+/// One scheduled trigger, e.g.
+/// ```toml
+/// [[schedule]]
+/// cron = "0 */5 * * * *"
+/// handler = "run"
+/// body = '{"hello": "world"}'
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScheduleEntry {
+    /// Cron expression with a leading seconds field
+    pub cron: String,
+    pub handler: String,
+    #[serde(default = "default_user")]
+    pub user: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default = "default_subpath")]
+    pub subpath: String,
+    /// Synthetic event body handed to the handler
+    #[serde(default)]
+    pub body: String,
+}
+
+fn default_user() -> String {
+    String::from("local")
+}
+
+fn default_method() -> String {
+    String::from("POST")
+}
+
+fn default_subpath() -> String {
+    String::from("/")
+}
+
+pub struct ScheduledTrigger {
+    schedule: Schedule,
+    method: Method,
+    entry: ScheduleEntry,
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ScheduledTrigger>> {
+    let content = std::fs::read_to_string(path)?;
+    let file: ScheduleFile = toml::from_str(&content)?;
+
+    file.schedule
+        .into_iter()
+        .map(|entry| {
+            let schedule = Schedule::from_str(&entry.cron)
+                .map_err(|e| anyhow::anyhow!("invalid cron `{}`: {e}", entry.cron))?;
+            let method = Method::from_bytes(entry.method.as_bytes())
+                .map_err(|e| anyhow::anyhow!("invalid method `{}`: {e}", entry.method))?;
+            Ok(ScheduledTrigger {
+                schedule,
+                method,
+                entry,
+            })
+        })
+        .collect()
+}
+
+/// Fire the trigger's handler every time its schedule comes up.
+pub async fn run(state: AppState, trigger: ScheduledTrigger) {
+    let entry = &trigger.entry;
+
+    while let Some(next) = trigger.schedule.upcoming(Utc).next() {
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        tracing::info!(
+            target: "flowsnet::schedule",
+            cron = %entry.cron,
+            handler = %entry.handler,
+            "firing scheduled trigger"
+        );
+
+        let event = FlowEvent {
+            flows_user: entry.user.clone(),
+            handler: entry.handler.clone(),
+            method: trigger.method.clone(),
+            headers: HeaderMap::new(),
+            subpath: entry.subpath.clone(),
+            query: EventQuery::default(),
+            body: Bytes::from(entry.body.clone()).into(),
+            client: ClientInfo::new(None, &HeaderMap::new(), &[]),
+        };
+        handler_inner(state.clone(), event, None).await;
+    }
+}
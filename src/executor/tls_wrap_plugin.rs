@@ -1,5 +1,16 @@
-use std::{collections::LinkedList, future::Future, sync::Arc};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::{
+    collections::LinkedList,
+    fs::File,
+    future::Future,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    time::timeout,
+};
 use wasmedge_sdk::{
     error::{CoreError, CoreExecutionError},
     r#async::{
@@ -9,6 +20,17 @@ use wasmedge_sdk::{
     CallingFrame, WasmEdgeResult, WasmValue,
 };
 
+use crate::cli::CassetteMode;
+use crate::executor::cassette::{Cassette, Exchange};
+use crate::executor::connection_pool::{ConnectionPool, PooledStream};
+use crate::executor::mock::{MockRules, Unmatched};
+use crate::executor::network_policy::NetworkPolicy;
+use crate::executor::outbound_error::OutboundError;
+use crate::executor::outbound_log::{OutboundCallRecord, OutboundDump};
+use crate::executor::raw_http::{self, BodyLength};
+use crate::metrics::METRICS;
+use crate::proxy::ProxyConfig;
+
 pub fn create_tls_wrap_import(
     https_data: WasmEdgeTlsReqData,
 ) -> WasmEdgeResult<ImportObject<WasmEdgeTlsReqData>> {
@@ -22,13 +44,62 @@ pub fn create_tls_wrap_import(
         wasmedge_httpsreq_get_rcv_len_,
     )?;
     builder.with_func::<i32, ()>("wasmedge_httpsreq_get_rcv", wasmedge_httpsreq_get_rcv_)?;
+    builder.with_func::<(i32, i32, i32, i32, i32), i32>(
+        "wasmedge_httpsreq_send_data_v2",
+        wasmedge_httpsreq_send_data_v2_,
+    )?;
+    builder.with_func::<(), i32>(
+        "wasmedge_httpsreq_get_err_len",
+        wasmedge_httpsreq_get_err_len_,
+    )?;
+    builder.with_func::<i32, ()>("wasmedge_httpsreq_get_err", wasmedge_httpsreq_get_err_)?;
     Ok(builder.build())
 }
 
+/// How the host module reaches the remote
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Tls,
+    Plain,
+}
+
 #[derive(Debug)]
 pub struct WasmEdgeTlsReqData {
+    transport: Transport,
     response: LinkedList<Vec<u8>>,
+    /// Message of the last failed `send_data_v2`
+    error: Option<String>,
     client_config: Arc<rustls::ClientConfig>,
+    cassette: Option<Arc<Cassette>>,
+    mock_rules: Option<Arc<MockRules>>,
+    dump: Option<Arc<OutboundDump>>,
+    policy: Option<Arc<NetworkPolicy>>,
+    limits: OutboundLimits,
+    pool: Arc<ConnectionPool>,
+    proxy: Option<Arc<ProxyConfig>>,
+    /// Collects the calls of one run for the dashboard
+    call_log: Option<Arc<Mutex<Vec<OutboundCallRecord>>>>,
+}
+
+/// Timeouts and size cap of a single outbound call
+#[derive(Clone, Copy, Debug)]
+pub struct OutboundLimits {
+    pub connect_timeout: Duration,
+    pub handshake_timeout: Duration,
+    /// Longest wait for the next bytes of the response
+    pub read_timeout: Duration,
+    pub max_response_size: usize,
+}
+
+impl Default for OutboundLimits {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            handshake_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(60),
+            max_response_size: 64 * 1024 * 1024,
+        }
+    }
 }
 
 impl Default for WasmEdgeTlsReqData {
@@ -40,18 +111,98 @@ impl Default for WasmEdgeTlsReqData {
 impl WasmEdgeTlsReqData {
     pub fn new(client_config: Arc<rustls::ClientConfig>) -> Self {
         Self {
+            transport: Transport::Tls,
             response: LinkedList::new(),
+            error: None,
             client_config,
+            cassette: None,
+            mock_rules: None,
+            dump: None,
+            policy: None,
+            limits: OutboundLimits::default(),
+            pool: Default::default(),
+            proxy: None,
+            call_log: None,
         }
     }
+
+    pub fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    pub fn with_mock_rules(mut self, mock_rules: Arc<MockRules>) -> Self {
+        self.mock_rules = Some(mock_rules);
+        self
+    }
+
+    pub fn with_dump(mut self, dump: Arc<OutboundDump>) -> Self {
+        self.dump = Some(dump);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Arc<NetworkPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Tunnel TLS connections through the proxy
+    pub fn with_proxy(mut self, proxy: Arc<ProxyConfig>) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn with_call_log(mut self, call_log: Arc<Mutex<Vec<OutboundCallRecord>>>) -> Self {
+        self.call_log = Some(call_log);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: OutboundLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The same data for a module which sends requests over plain TCP
+    pub fn plain(mut self) -> Self {
+        self.transport = Transport::Plain;
+        self
+    }
+
+    /// Whether the `rustls_client` plugin may be registered next to this module
+    pub fn rustls_plugin_allowed(&self) -> bool {
+        self.policy
+            .as_ref()
+            .is_none_or(|policy| policy.allow_rustls_plugin)
+    }
 }
 impl Clone for WasmEdgeTlsReqData {
     fn clone(&self) -> Self {
-        Self::new(self.client_config.clone())
+        Self {
+            transport: self.transport,
+            response: LinkedList::new(),
+            error: None,
+            client_config: self.client_config.clone(),
+            cassette: self.cassette.clone(),
+            mock_rules: self.mock_rules.clone(),
+            dump: self.dump.clone(),
+            policy: self.policy.clone(),
+            limits: self.limits,
+            pool: self.pool.clone(),
+            proxy: self.proxy.clone(),
+            call_log: self.call_log.clone(),
+        }
     }
 }
 
 pub fn default_client_config() -> Arc<rustls::ClientConfig> {
+    let client_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(webpki_root_store())
+        .with_no_client_auth();
+    Arc::new(client_config)
+}
+
+fn webpki_root_store() -> rustls::RootCertStore {
     let mut root_store = rustls::RootCertStore::empty();
     root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
         rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
@@ -60,16 +211,78 @@ pub fn default_client_config() -> Arc<rustls::ClientConfig> {
             ta.name_constraints,
         )
     }));
-    let client_config = rustls::ClientConfig::builder()
+    root_store
+}
+
+/// Extra trust and client authentication for the guest's TLS connections.
+#[derive(Clone, Debug, Default)]
+pub struct TlsOptions {
+    /// PEM bundles of CA certificates trusted on top of the webpki roots
+    pub ca_certs: Vec<PathBuf>,
+    /// Also trust the operating system's root certificates
+    pub system_roots: bool,
+    /// PEM certificate chain and private key presented for mTLS
+    pub client_cert: Option<(PathBuf, PathBuf)>,
+}
+
+pub fn client_config(options: &TlsOptions) -> anyhow::Result<Arc<rustls::ClientConfig>> {
+    let mut root_store = webpki_root_store();
+
+    if options.system_roots {
+        let certs = rustls_native_certs::load_native_certs()
+            .map_err(|e| anyhow::anyhow!("Failed to load system root certificates: {e}"))?;
+        let (_, ignored) = root_store.add_parsable_certificates(&certs);
+        if ignored > 0 {
+            tracing::warn!("{ignored} system root certificates could not be parsed");
+        }
+    }
+
+    for ca_cert in options.ca_certs.iter() {
+        for cert in read_pem_certs(ca_cert)? {
+            root_store.add(&cert).map_err(|e| {
+                anyhow::anyhow!("Invalid CA certificate in {}: {e}", ca_cert.display())
+            })?;
+        }
+    }
+
+    let builder = rustls::ClientConfig::builder()
         .with_safe_defaults()
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
-    Arc::new(client_config)
+        .with_root_certificates(root_store);
+
+    let client_config = match options.client_cert.as_ref() {
+        Some((cert, key)) => {
+            builder.with_client_auth_cert(read_pem_certs(cert)?, read_pem_key(key)?)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(client_config))
+}
+
+fn read_pem_certs(path: &Path) -> anyhow::Result<Vec<rustls::Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in {}", path.display());
+    }
+    Ok(certs.into_iter().map(rustls::Certificate).collect())
+}
+
+fn read_pem_key(path: &Path) -> anyhow::Result<rustls::PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::read_all(&mut reader)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path.display()))
 }
 
 type AsyncReturn<'fut> = Box<dyn Future<Output = Result<Vec<WasmValue>, CoreError>> + Send + 'fut>;
 
-fn wasmedge_httpsreq_send_data_<'a>(
+pub(crate) fn wasmedge_httpsreq_send_data_<'a>(
     data: &'a mut WasmEdgeTlsReqData,
     _inst: &mut AsyncInstance,
     frame: &'a mut CallingFrame,
@@ -83,6 +296,55 @@ async fn wasmedge_httpsreq_send_data(
     frame: &mut CallingFrame,
     args: Vec<WasmValue>,
 ) -> Result<Vec<WasmValue>, CoreError> {
+    let resp = match send_guest_request(data, frame, args).await? {
+        Ok(resp) => resp,
+        Err(e) => e
+            .to_response()
+            .ok_or(CoreError::Execution(CoreExecutionError::HostFuncFailed))?,
+    };
+    data.response.push_back(resp);
+
+    Ok(vec![])
+}
+
+pub(crate) fn wasmedge_httpsreq_send_data_v2_<'a>(
+    data: &'a mut WasmEdgeTlsReqData,
+    _inst: &mut AsyncInstance,
+    frame: &'a mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> AsyncReturn<'a> {
+    Box::new(wasmedge_httpsreq_send_data_v2(data, frame, args))
+}
+
+/// Like `send_data`, but failures are returned as a non-zero status with the
+/// message left for `get_err`, instead of trapping the guest.
+async fn wasmedge_httpsreq_send_data_v2(
+    data: &mut WasmEdgeTlsReqData,
+    frame: &mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    let code = match send_guest_request(data, frame, args).await? {
+        Ok(resp) => {
+            data.error = None;
+            data.response.push_back(resp);
+            0
+        }
+        Err(e) => {
+            data.error = Some(e.to_string());
+            e.code()
+        }
+    };
+
+    Ok(vec![WasmValue::from_i32(code)])
+}
+
+/// Read the guest's request from its memory and send it. The outer `Err` is for
+/// bad arguments of the guest which trap it.
+async fn send_guest_request(
+    data: &mut WasmEdgeTlsReqData,
+    frame: &mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> Result<Result<Vec<u8>, OutboundError>, CoreError> {
     let memory = frame
         .memory_ref(0)
         .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
@@ -109,39 +371,346 @@ async fn wasmedge_httpsreq_send_data(
             CoreExecutionError::MemoryOutOfBounds,
         )))?;
 
-    let resp = tls_send(data.client_config.clone(), host, port as u16, body)
-        .await
-        .or(Err(CoreError::Execution(
-            CoreExecutionError::HostFuncFailed,
-        )))?;
+    let host = match String::from_utf8(host) {
+        Ok(host) => host,
+        Err(e) => {
+            let host = String::from_utf8_lossy(e.as_bytes()).into_owned();
+            return Ok(Err(OutboundError::InvalidHost(host)));
+        }
+    };
 
-    data.response.push_back(resp);
+    let port = port as u16;
+    let started = Instant::now();
+    let resp = send(data, &host, port, &body).await;
 
-    Ok(vec![])
+    let record = OutboundCallRecord::new(
+        &host,
+        port,
+        &body,
+        resp.as_deref().map_err(|e| e.to_string()),
+        started.elapsed(),
+    );
+    record.emit();
+
+    let outcome = match (&resp, record.status) {
+        (Err(e), _) => e.kind().to_string(),
+        (Ok(_), Some(status)) => status.to_string(),
+        (Ok(_), None) => String::from("invalid-response"),
+    };
+    let host_label = METRICS.host_label(&host);
+    METRICS
+        .outbound_requests
+        .with_label_values(&[host_label.as_str(), outcome.as_str()])
+        .inc();
+    METRICS
+        .outbound_duration
+        .with_label_values(&[host_label.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    if let Some(call_log) = data.call_log.as_ref() {
+        if let Ok(mut calls) = call_log.lock() {
+            calls.push(record);
+        }
+    }
+
+    if let Some(dump) = data.dump.as_ref() {
+        let resp = match resp.as_ref() {
+            Ok(resp) => Some(resp.clone()),
+            Err(e) => e.to_response(),
+        };
+        if let Some(resp) = resp {
+            dump.write(&host, port, &body, &resp).await;
+        }
+    }
+
+    Ok(resp)
 }
 
-async fn tls_send(
-    client_config: Arc<rustls::ClientConfig>,
-    host: Vec<u8>,
+/// Answer a guest request from the mock rules or the cassette, or send it out.
+async fn send(
+    data: &WasmEdgeTlsReqData,
+    host: &str,
+    port: u16,
+    body: &[u8],
+) -> Result<Vec<u8>, OutboundError> {
+    if let Some(rules) = data.mock_rules.as_ref() {
+        match rules.find(host, port, body) {
+            Some(rule) => {
+                tracing::debug!(
+                    target: "flowsnet::mock",
+                    "request to {host}:{port} answered by a mock rule with status {}",
+                    rule.status
+                );
+                return Ok(rule.response());
+            }
+            None if rules.unmatched == Unmatched::Reject => {
+                return Err(OutboundError::NoMatch(String::from(
+                    "no mock rule matches the request",
+                )));
+            }
+            None => {}
+        }
+    }
+
+    match data.cassette.as_ref() {
+        Some(cassette) if cassette.mode() == CassetteMode::Replay => {
+            cassette.replay(host, port, body).ok_or_else(|| {
+                OutboundError::NoMatch(String::from("no recorded exchange matches the request"))
+            })
+        }
+        cassette => {
+            let resp = exchange(data, host, port, body).await?;
+
+            if let Some(cassette) = cassette {
+                let exchange = Exchange {
+                    host: host.to_string(),
+                    port,
+                    request: body.to_vec(),
+                    response: resp.clone(),
+                };
+                if let Err(e) = cassette.record(&exchange).await {
+                    tracing::warn!(target: "flowsnet::cassette", "Failed to record exchange: {e}");
+                }
+            }
+            Ok(resp)
+        }
+    }
+}
+
+/// Send the request over an idle pooled connection to the remote, or a new one,
+/// and keep the connection for the next call if the response allows it.
+async fn exchange(
+    data: &WasmEdgeTlsReqData,
+    host: &str,
     port: u16,
-    body: Vec<u8>,
-) -> std::io::Result<Vec<u8>> {
-    let connector = tokio_rustls::TlsConnector::from(client_config);
-    let host = String::from_utf8(host)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-    let domain = rustls::ServerName::try_from(host.as_str())
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid dnsname"))?;
-    let addr = (host, port);
-    let stream = tokio::net::TcpStream::connect(&addr).await?;
-    let mut stream = connector.connect(domain, stream).await?;
-    stream.write_all(&body).await?;
-    stream.flush().await?;
+    body: &[u8],
+) -> Result<Vec<u8>, OutboundError> {
+    let head = raw_http::parse_request_head(body);
+    let head_request = head
+        .as_ref()
+        .is_some_and(|h| h.method.eq_ignore_ascii_case("HEAD"));
+    let close = head.as_ref().is_some_and(raw_http::wants_close);
+    let idempotent = head
+        .as_ref()
+        .is_some_and(|h| raw_http::is_idempotent(&h.method));
+
+    // only for requests which really go out, mocks and cassettes need no network
+    if let Some(policy) = data.policy.as_ref() {
+        policy
+            .check_host(host, port, data.transport)
+            .map_err(denied)?;
+    }
+
+    if let Some(mut stream) = data.pool.take(data.transport, host, port) {
+        // a request which could not be written was never seen by the remote,
+        // the idle connection was gone already
+        if write_request(&mut stream, body).await.is_ok() {
+            match read_response(&mut stream, &data.limits, head_request).await? {
+                Some((resp, keep_alive)) => {
+                    if keep_alive && !close {
+                        data.pool.put(data.transport, host, port, stream);
+                    }
+                    return Ok(resp);
+                }
+                // closed without an answer, either before or after the remote
+                // saw the request, so it is only sent again if that's harmless
+                None if idempotent => {}
+                None => return Err(no_response()),
+            }
+        }
+    }
+
+    let mut stream = match data.transport {
+        Transport::Tls => tls_connect(data, host, port).await?,
+        Transport::Plain => {
+            let policy = data
+                .policy
+                .as_deref()
+                .map(|policy| (policy, data.transport));
+            PooledStream::Plain(connect(&data.limits, host, port, policy).await?)
+        }
+    };
+    write_request(&mut stream, body).await?;
+    let (resp, keep_alive) = read_response(&mut stream, &data.limits, head_request)
+        .await?
+        .ok_or_else(no_response)?;
+    if keep_alive && !close {
+        data.pool.put(data.transport, host, port, stream);
+    }
+    Ok(resp)
+}
+
+fn denied(reason: String) -> OutboundError {
+    tracing::warn!(target: "flowsnet::policy", "{reason}");
+    OutboundError::Denied(reason)
+}
+
+/// Connect to the first reachable address of `host`. With a `policy`, each
+/// address is checked right before connecting to it.
+async fn connect(
+    limits: &OutboundLimits,
+    host: &str,
+    port: u16,
+    policy: Option<(&NetworkPolicy, Transport)>,
+) -> Result<tokio::net::TcpStream, OutboundError> {
+    let connect = async {
+        let addrs = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(OutboundError::Resolve)?;
+        let mut last_err = None;
+        let mut refused = None;
+        for addr in addrs {
+            if let Some((policy, transport)) = policy {
+                if let Err(reason) = policy.check_addr(host, &addr, transport) {
+                    refused = Some(reason);
+                    continue;
+                }
+            }
+            match tokio::net::TcpStream::connect(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        if let (None, Some(reason)) = (last_err.as_ref(), refused) {
+            return Err(denied(reason));
+        }
+        Err(match last_err {
+            Some(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                OutboundError::ConnectionRefused
+            }
+            Some(e) => OutboundError::Io(e),
+            None => OutboundError::Resolve(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no addresses found",
+            )),
+        })
+    };
+    timeout(limits.connect_timeout, connect)
+        .await
+        .map_err(|_| OutboundError::ConnectTimeout)?
+}
+
+/// Sessions are resumed from the cache of the shared `client_config`.
+async fn tls_connect(
+    data: &WasmEdgeTlsReqData,
+    host: &str,
+    port: u16,
+) -> Result<PooledStream, OutboundError> {
+    let limits = &data.limits;
+    let connector = tokio_rustls::TlsConnector::from(data.client_config.clone());
+    let domain = rustls::ServerName::try_from(host)
+        .map_err(|_| OutboundError::InvalidHost(host.to_string()))?;
+    let stream = match data.proxy.as_ref().filter(|proxy| !proxy.bypass(host)) {
+        Some(proxy) => {
+            if let Some(policy) = data.policy.as_ref() {
+                policy
+                    .check_resolved(host, port, data.transport)
+                    .await
+                    .map_err(denied)?;
+            }
+            let mut stream = connect(limits, &proxy.host, proxy.port, None).await?;
+            timeout(
+                limits.handshake_timeout,
+                proxy.tunnel(&mut stream, host, port),
+            )
+            .await
+            .map_err(|_| OutboundError::HandshakeTimeout)?
+            .map_err(OutboundError::Proxy)?;
+            stream
+        }
+        None => {
+            let policy = data
+                .policy
+                .as_deref()
+                .map(|policy| (policy, data.transport));
+            connect(limits, host, port, policy).await?
+        }
+    };
+    let stream = timeout(limits.handshake_timeout, connector.connect(domain, stream))
+        .await
+        .map_err(|_| OutboundError::HandshakeTimeout)?
+        .map_err(OutboundError::Tls)?;
+    Ok(PooledStream::Tls(Box::new(stream)))
+}
+
+async fn write_request(stream: &mut PooledStream, body: &[u8]) -> std::io::Result<()> {
+    stream.write_all(body).await?;
+    stream.flush().await
+}
+
+fn no_response() -> OutboundError {
+    OutboundError::Io(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "the connection closed without a response",
+    ))
+}
+
+/// Read one complete response, framed by `Content-Length` or chunked encoding,
+/// or until the remote closes the connection. Also returns whether the
+/// connection can be reused. `None` when the connection ended before any byte
+/// arrived; one closed in the middle of a framed response is an error.
+async fn read_response<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    limits: &OutboundLimits,
+    head_request: bool,
+) -> Result<Option<(Vec<u8>, bool)>, OutboundError> {
     let mut buf = vec![];
-    let _ = stream.read_to_end(&mut buf).await;
-    Ok(buf)
+    let mut chunk = vec![0; 16 * 1024];
+    let mut head = None;
+    loop {
+        if head.is_none() {
+            head = raw_http::parse_response_head(&buf, head_request)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        }
+        if let Some(head) = head {
+            let end = match head.body {
+                BodyLength::Empty => Some(head.len),
+                BodyLength::Fixed(len) => {
+                    let end = head
+                        .len
+                        .checked_add(len)
+                        .ok_or(OutboundError::ResponseTooLarge(limits.max_response_size))?;
+                    Some(end).filter(|end| *end <= buf.len())
+                }
+                BodyLength::Chunked => raw_http::chunked_body_len(&buf[head.len..])
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+                    .map(|len| head.len + len),
+                BodyLength::UntilClose => None,
+            };
+            if let Some(end) = end {
+                buf.truncate(end);
+                return Ok(Some((buf, head.keep_alive)));
+            }
+        }
+
+        let until_close = head.is_some_and(|head| head.body == BodyLength::UntilClose);
+        let n = match timeout(limits.read_timeout, stream.read(&mut chunk)).await {
+            Err(_) => return Err(OutboundError::ReadTimeout),
+            Ok(Ok(n)) => n,
+            // a body running until the close may end without `close_notify`
+            Ok(Err(e)) if until_close && e.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+            Ok(Err(_)) if buf.is_empty() => return Ok(None),
+            Ok(Err(e)) => return Err(OutboundError::Io(e)),
+        };
+        if n == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            if until_close {
+                return Ok(Some((buf, false)));
+            }
+            return Err(OutboundError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the connection closed in the middle of the response",
+            )));
+        }
+        if buf.len() + n > limits.max_response_size {
+            return Err(OutboundError::ResponseTooLarge(limits.max_response_size));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
 }
 
-fn wasmedge_httpsreq_get_rcv_len_<'a>(
+pub(crate) fn wasmedge_httpsreq_get_rcv_len_<'a>(
     data: &'a mut WasmEdgeTlsReqData,
     _inst: &mut AsyncInstance,
     _frame: &mut CallingFrame,
@@ -158,7 +727,7 @@ async fn wasmedge_httpsreq_get_rcv_len(
     )])
 }
 
-fn wasmedge_httpsreq_get_rcv_<'a>(
+pub(crate) fn wasmedge_httpsreq_get_rcv_<'a>(
     data: &'a mut WasmEdgeTlsReqData,
     _inst: &mut AsyncInstance,
     frame: &'a mut CallingFrame,
@@ -193,3 +762,55 @@ async fn wasmedge_httpsreq_get_rcv(
 
     Ok(vec![])
 }
+
+pub(crate) fn wasmedge_httpsreq_get_err_len_<'a>(
+    data: &'a mut WasmEdgeTlsReqData,
+    _inst: &mut AsyncInstance,
+    _frame: &mut CallingFrame,
+    _args: Vec<WasmValue>,
+) -> AsyncReturn<'a> {
+    Box::new(wasmedge_httpsreq_get_err_len(data))
+}
+
+async fn wasmedge_httpsreq_get_err_len(
+    data: &mut WasmEdgeTlsReqData,
+) -> Result<Vec<WasmValue>, CoreError> {
+    Ok(vec![WasmValue::from_i32(
+        data.error.as_ref().map(|e| e.len() as i32).unwrap_or(0),
+    )])
+}
+
+pub(crate) fn wasmedge_httpsreq_get_err_<'a>(
+    data: &'a mut WasmEdgeTlsReqData,
+    _inst: &mut AsyncInstance,
+    frame: &'a mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> AsyncReturn<'a> {
+    Box::new(wasmedge_httpsreq_get_err(data, frame, args))
+}
+
+/// Copy the message of the last failed `send_data_v2` into the guest's memory
+async fn wasmedge_httpsreq_get_err(
+    data: &mut WasmEdgeTlsReqData,
+    frame: &mut CallingFrame,
+    args: Vec<WasmValue>,
+) -> Result<Vec<WasmValue>, CoreError> {
+    let mut memory = frame
+        .memory_mut(0)
+        .ok_or(CoreError::Execution(CoreExecutionError::MemoryOutOfBounds))?;
+
+    if args.len() != 1 {
+        return Err(CoreError::Execution(CoreExecutionError::FuncTypeMismatch));
+    }
+    let err_ptr = args[0].to_i32() as u32;
+
+    if let Some(error) = data.error.take() {
+        memory
+            .set_data(error.into_bytes(), err_ptr)
+            .or(Err(CoreError::Execution(
+                CoreExecutionError::MemoryOutOfBounds,
+            )))?;
+    }
+
+    Ok(vec![])
+}
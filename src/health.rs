@@ -0,0 +1,199 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::metrics::METRICS;
+
+/// Updated by the tunnel and heartbeat tasks in `main` and the executor, read
+/// by the `/_flowsnet/healthz` and `/_flowsnet/readyz` endpoints.
+pub static HEALTH: Lazy<RwLock<Health>> = Lazy::new(Default::default);
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Health {
+    pub wasm: WasmStatus,
+    pub tunnel: TunnelStatus,
+    /// `None` until the first heartbeat has been sent
+    pub last_heartbeat: Option<Heartbeat>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum WasmStatus {
+    #[default]
+    Unchecked,
+    Loaded,
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum TunnelStatus {
+    #[default]
+    Connecting,
+    Running,
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Heartbeat {
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl Health {
+    /// Reasons the flow can't serve platform events yet, empty when ready
+    pub fn not_ready(&self) -> Vec<String> {
+        let mut reasons = vec![];
+        match &self.wasm {
+            WasmStatus::Loaded => {}
+            WasmStatus::Unchecked => reasons.push(String::from("the wasm is not loaded yet")),
+            WasmStatus::Failed(e) => reasons.push(format!("the wasm failed to load: {e}")),
+        }
+        match &self.tunnel {
+            TunnelStatus::Running => {}
+            TunnelStatus::Connecting => reasons.push(String::from("the tunnel is not up yet")),
+            TunnelStatus::Failed(e) => reasons.push(format!("the tunnel is down: {e}")),
+        }
+        if let Some(Heartbeat {
+            ok: false, error, ..
+        }) = &self.last_heartbeat
+        {
+            reasons.push(format!(
+                "the last heartbeat failed: {}",
+                error.as_deref().unwrap_or_default()
+            ));
+        }
+        reasons
+    }
+}
+
+pub fn update(f: impl FnOnce(&mut Health)) {
+    if let Ok(mut health) = HEALTH.write() {
+        f(&mut health);
+    }
+}
+
+pub fn snapshot() -> Health {
+    HEALTH
+        .read()
+        .map(|health| health.clone())
+        .unwrap_or_default()
+}
+
+pub fn record_heartbeat(result: Result<(), String>) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let (ok, error) = match result {
+        Ok(()) => (true, None),
+        Err(e) => (false, Some(e)),
+    };
+    update(|health| {
+        health.last_heartbeat = Some(Heartbeat {
+            timestamp,
+            ok,
+            error,
+        })
+    });
+}
+
+/// Follows the tunnel's state through rathole's log, as `rathole::run` reports
+/// nothing else until it stops: the tunnel only counts as running once the
+/// control channel is established, and as failed when the control channel
+/// fails. Every control channel after the first counts as a reconnect.
+///
+/// The messages matched are those of the rathole version pinned in
+/// `Cargo.toml`, see the tests below when upgrading it.
+#[derive(Default)]
+pub struct TunnelWatch {
+    connected: AtomicBool,
+}
+
+/// Where rathole's client logs its control channel
+const TUNNEL_TARGET: &str = "rathole::client";
+
+/// What `TunnelWatch` needs to see, independent of the filter of the log output
+pub fn tunnel_log_filter() -> Targets {
+    Targets::new().with_target(TUNNEL_TARGET, Level::INFO)
+}
+
+impl<S: Subscriber> Layer<S> for TunnelWatch {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() != TUNNEL_TARGET {
+            return;
+        }
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        let message = message.0;
+
+        if message.starts_with("Control channel established") {
+            if self.connected.swap(true, Ordering::Relaxed) {
+                METRICS.tunnel_reconnects.inc();
+            }
+            update(|health| health.tunnel = TunnelStatus::Running);
+        } else if *metadata.level() == Level::ERROR
+            && message.starts_with("Failed to run the control channel")
+        {
+            // data channel errors only affect one connection of the service
+            update(|health| health.tunnel = TunnelStatus::Failed(message));
+        }
+    }
+}
+
+struct MessageVisitor(String);
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn tunnel() -> TunnelStatus {
+        snapshot().tunnel
+    }
+
+    #[test]
+    fn tunnel_watch() {
+        let subscriber = tracing_subscriber::registry()
+            .with(TunnelWatch::default().with_filter(tunnel_log_filter()));
+        let reconnects = METRICS.tunnel_reconnects.get();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "rathole::client", "Control channel established");
+            assert!(matches!(tunnel(), TunnelStatus::Running));
+
+            tracing::error!(
+                target: "rathole::client",
+                "Failed to run the data channel: Connection refused"
+            );
+            tracing::error!(target: "flowsnet::flow", "Failed to run the control channel");
+            assert!(matches!(tunnel(), TunnelStatus::Running));
+
+            tracing::error!(
+                target: "rathole::client",
+                "Failed to run the control channel: Connection refused. Retry in 1s..."
+            );
+            assert!(matches!(tunnel(), TunnelStatus::Failed(_)));
+
+            tracing::info!(target: "rathole::client", "Control channel established");
+            assert!(matches!(tunnel(), TunnelStatus::Running));
+        });
+        assert_eq!(METRICS.tunnel_reconnects.get(), reconnects + 1);
+    }
+}
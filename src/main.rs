@@ -1,17 +1,23 @@
 mod cli;
 mod executor;
+mod health;
+mod metrics;
+mod proxy;
 
 use clap::Parser;
-use cli::Cli;
+use cli::{Cli, LogFormat};
+use health::{TunnelStatus, TunnelWatch};
 use lazy_static::lazy_static;
-use once_cell::sync::Lazy;
+use metrics::METRICS;
+use once_cell::sync::{Lazy, OnceCell};
+use proxy::ProxyConfig;
 use reqwest::{Client, ClientBuilder};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 use tokio::{signal, sync::broadcast};
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 const TIMEOUT: u64 = 30;
 const SERVER_HOST: &str = "dev.flows.network";
@@ -19,15 +25,69 @@ const SERVER_API_URL: Lazy<String> = Lazy::new(|| format!("https://{}", SERVER_H
 
 lazy_static! {
     static ref HEART_INTERVAL: Duration = Duration::from_secs(30);
-    pub static ref HTTP_CLIENT: Client = ClientBuilder::new()
+}
+
+/// Client of the platform API, built once the proxy settings are known
+static HTTP_CLIENT: OnceCell<Client> = OnceCell::new();
+
+fn http_client() -> &'static Client {
+    HTTP_CLIENT
+        .get()
+        .expect("the reqwest client is not built yet")
+}
+
+fn build_http_client(proxy: Option<&ProxyConfig>) -> anyhow::Result<Client> {
+    // the proxy environment is read by `ProxyConfig` so the CLI override wins
+    let builder = ClientBuilder::new()
         .timeout(Duration::from_secs(TIMEOUT))
-        .build()
-        .expect("Can't build the reqwest client");
+        .no_proxy();
+    let builder = match proxy {
+        Some(proxy) => builder.proxy(proxy.reqwest_proxy()?),
+        None => builder,
+    };
+    Ok(builder.build()?)
 }
 
 #[tokio::main]
 async fn main() {
     let args = Cli::parse();
+
+    {
+        let is_atty = atty::is(atty::Stream::Stdout);
+
+        let level = "info"; // if RUST_LOG not present, use `info` level
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::from(level));
+        let fmt = tracing_subscriber::fmt::layer();
+        let fmt = match args.log_format {
+            LogFormat::Text => fmt.with_ansi(is_atty).with_filter(filter).boxed(),
+            LogFormat::Json => fmt.json().with_ansi(false).with_filter(filter).boxed(),
+        };
+        // `RUST_LOG` only filters the output, `TunnelWatch` sees rathole's log either way
+        tracing_subscriber::registry()
+            .with(fmt)
+            .with(TunnelWatch::default().with_filter(health::tunnel_log_filter()))
+            .init();
+    }
+
+    let client = ProxyConfig::from_cli(&args).and_then(|proxy| build_http_client(proxy.as_ref()));
+    match client {
+        Ok(client) => {
+            _ = HTTP_CLIENT.set(client);
+        }
+        Err(e) => {
+            eprintln!("Failed to set up the proxy: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(event_file) = args.replay.clone() {
+        if let Err(e) = executor::replay(args, event_file).await {
+            eprintln!("Failed to replay event: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let (shutdown_tx, shutdown_rx) = broadcast::channel::<bool>(1);
     let rx = shutdown_tx.subscribe();
     let rx2 = shutdown_tx.subscribe();
@@ -66,25 +126,21 @@ async fn main() {
         }
     });
 
-    {
-        let is_atty = atty::is(atty::Stream::Stdout);
-
-        let level = "info"; // if RUST_LOG not present, use `info` level
-        tracing_subscriber::fmt()
-            .with_env_filter(
-                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::from(level)),
-            )
-            .with_ansi(is_atty)
-            .init();
-    }
-
     run_proxy(&args, shutdown_rx).await;
 
     heart(&args.flow, rx2).await;
 }
 
 async fn run_proxy(args: &Cli, shutdown_rx: broadcast::Receiver<bool>) {
-    let link_result = link(&args.flow).await.unwrap();
+    let link_result = match link(&args.flow).await {
+        Ok(link_result) => link_result,
+        Err(e) => {
+            // keep serving locally, `/_flowsnet/readyz` reports the tunnel as down
+            tracing::error!("Failed to link the flow to {SERVER_HOST}: {e}");
+            health::update(|health| health.tunnel = TunnelStatus::Failed(e.to_string()));
+            return;
+        }
+    };
 
     let config = Config {
         client: ConfigClient {
@@ -113,7 +169,13 @@ async fn run_proxy(args: &Cli, shutdown_rx: broadcast::Receiver<bool>) {
             genkey: None,
         };
 
-        _ = rathole::run(args, shutdown_rx).await;
+        // `TunnelWatch` reports it running once it has connected
+        health::update(|health| health.tunnel = TunnelStatus::Connecting);
+        let error = match rathole::run(args, shutdown_rx).await {
+            Ok(()) => String::from("the tunnel has stopped"),
+            Err(e) => e.to_string(),
+        };
+        health::update(|health| health.tunnel = TunnelStatus::Failed(error));
     });
 }
 
@@ -139,7 +201,7 @@ struct LinkResult {
 }
 
 async fn link(flow: &str) -> anyhow::Result<LinkResult> {
-    let response = HTTP_CLIENT
+    let response = http_client()
         .post(format!("{}/link/{}", SERVER_API_URL.as_str(), flow))
         .send()
         .await;
@@ -153,10 +215,17 @@ async fn heart(flow: &str, mut shutdown_rx: broadcast::Receiver<bool>) {
     loop {
         tokio::select! {
             _ = tokio::time::sleep(*HEART_INTERVAL) => {
-                _ = HTTP_CLIENT
+                let result = http_client()
                     .post(format!("{}/heart/{}", SERVER_API_URL.as_str(), flow))
                     .send()
-                    .await;
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .map(|_| ())
+                    .map_err(|e| e.to_string());
+                if result.is_err() {
+                    METRICS.heartbeat_failures.inc();
+                }
+                health::record_heartbeat(result);
             }
             _ = shutdown_rx.recv() => break
         }
@@ -0,0 +1,140 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Distinct outbound hosts labelled by name, later ones are labelled `other`
+const MAX_HOST_LABELS: usize = 100;
+
+/// Every metric of the executor, served on `/_flowsnet/metrics`.
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    /// By handler function and status
+    pub requests: IntCounterVec,
+    pub request_duration: HistogramVec,
+    /// Time spent running the wasm, by handler function
+    pub wasm_execution: HistogramVec,
+    /// Outbound calls of the guest, by host and outcome (status or error kind)
+    pub outbound_requests: IntCounterVec,
+    pub outbound_duration: HistogramVec,
+    pub vfs_read_bytes: IntCounter,
+    pub vfs_written_bytes: IntCounter,
+    pub tunnel_reconnects: IntCounter,
+    pub heartbeat_failures: IntCounter,
+    /// Hosts which got their own label
+    hosts: Mutex<HashSet<String>>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some(String::from("flowsnet")), None)
+            .expect("valid metrics prefix");
+
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "Handler runs"),
+            &["handler", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "request_duration_seconds",
+                "Time from receiving a request to its response",
+            ),
+            &["handler", "status"],
+        )
+        .unwrap();
+        let wasm_execution = HistogramVec::new(
+            HistogramOpts::new("wasm_execution_seconds", "Time spent running the wasm"),
+            &["handler"],
+        )
+        .unwrap();
+        let outbound_requests = IntCounterVec::new(
+            Opts::new("outbound_requests_total", "Outbound calls of the guest"),
+            &["host", "outcome"],
+        )
+        .unwrap();
+        let outbound_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "outbound_request_duration_seconds",
+                "Latency of the guest's outbound calls",
+            ),
+            &["host"],
+        )
+        .unwrap();
+        let vfs_read_bytes =
+            IntCounter::new("vfs_read_bytes_total", "Bytes the guest read from files").unwrap();
+        let vfs_written_bytes =
+            IntCounter::new("vfs_written_bytes_total", "Bytes the guest wrote to files").unwrap();
+        let tunnel_reconnects =
+            IntCounter::new("tunnel_reconnects_total", "Reconnects of the tunnel").unwrap();
+        let heartbeat_failures =
+            IntCounter::new("heartbeat_failures_total", "Failed heartbeats").unwrap();
+
+        for collector in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(request_duration.clone()),
+            Box::new(wasm_execution.clone()),
+            Box::new(outbound_requests.clone()),
+            Box::new(outbound_duration.clone()),
+            Box::new(vfs_read_bytes.clone()),
+            Box::new(vfs_written_bytes.clone()),
+            Box::new(tunnel_reconnects.clone()),
+            Box::new(heartbeat_failures.clone()),
+        ] {
+            registry.register(collector).expect("unique metric names");
+        }
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            wasm_execution,
+            outbound_requests,
+            outbound_duration,
+            vfs_read_bytes,
+            vfs_written_bytes,
+            tunnel_reconnects,
+            heartbeat_failures,
+            hosts: Default::default(),
+        }
+    }
+
+    /// Label for an outbound host, which the guest picks freely: normalised,
+    /// and `other` once `MAX_HOST_LABELS` hosts have been seen.
+    pub fn host_label(&self, host: &str) -> String {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let valid = !host.is_empty()
+            && host.len() <= 253
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+        if !valid {
+            return String::from("invalid");
+        }
+
+        let Ok(mut hosts) = self.hosts.lock() else {
+            return String::from("other");
+        };
+        if hosts.contains(&host) {
+            return host;
+        }
+        if hosts.len() >= MAX_HOST_LABELS {
+            return String::from("other");
+        }
+        hosts.insert(host.clone());
+        host
+    }
+
+    /// Text exposition format
+    pub fn render(&self) -> String {
+        let mut buf = vec![];
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            tracing::warn!("Failed to encode metrics: {e}");
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
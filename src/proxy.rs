@@ -0,0 +1,165 @@
+use base64::Engine;
+use reqwest::Url;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::cli::Cli;
+
+/// Longest response head accepted from the proxy for a `CONNECT`
+const MAX_CONNECT_RESPONSE: usize = 8 * 1024;
+
+/// HTTP proxy the CLI's platform calls and the guest's TLS connections go
+/// through, from `--proxy`/`--no-proxy` or `HTTPS_PROXY`/`NO_PROXY`.
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    url: Url,
+    pub host: String,
+    pub port: u16,
+    /// Decoded user and password of the proxy URL
+    auth: Option<(String, String)>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// `None` when no proxy is configured, or `NO_PROXY` is `*`.
+    pub fn from_cli(cli: &Cli) -> anyhow::Result<Option<Self>> {
+        let url = match cli.proxy.clone().or_else(|| env_var("HTTPS_PROXY")) {
+            Some(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let no_proxy = cli
+            .no_proxy
+            .clone()
+            .or_else(|| env_var("NO_PROXY"))
+            .unwrap_or_default();
+        let no_proxy = no_proxy
+            .split(',')
+            .map(|entry| entry.trim().to_ascii_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .collect::<Vec<_>>();
+        if no_proxy.iter().any(|entry| entry == "*") {
+            return Ok(None);
+        }
+
+        // `host:port` without a scheme is common in `HTTPS_PROXY`
+        let url = match url.contains("://") {
+            true => Url::parse(&url),
+            false => Url::parse(&format!("http://{url}")),
+        }
+        .map_err(|e| anyhow::anyhow!("invalid proxy URL `{url}`: {e}"))?;
+        if url.scheme() != "http" {
+            anyhow::bail!(
+                "unsupported proxy scheme `{}`, only http:// proxies are supported",
+                url.scheme()
+            );
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("proxy URL `{url}` has no host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = url.port().unwrap_or(80);
+        let auth = match url.username() {
+            "" => None,
+            user => Some((
+                percent_decode(user),
+                percent_decode(url.password().unwrap_or_default()),
+            )),
+        };
+
+        Ok(Some(Self {
+            url,
+            host,
+            port,
+            auth,
+            no_proxy,
+        }))
+    }
+
+    /// Whether connections to `host` skip the proxy
+    pub fn bypass(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.no_proxy.iter().any(|entry| {
+            let domain = entry.trim_start_matches("*.").trim_start_matches('.');
+            host == domain || host.ends_with(&format!(".{domain}"))
+        })
+    }
+
+    /// Proxy settings for a reqwest client
+    pub fn reqwest_proxy(&self) -> reqwest::Result<reqwest::Proxy> {
+        let mut url = self.url.clone();
+        _ = url.set_username("");
+        _ = url.set_password(None);
+
+        let mut proxy = reqwest::Proxy::all(url.as_str())?
+            .no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(",")));
+        if let Some((user, password)) = self.auth.as_ref() {
+            proxy = proxy.basic_auth(user, password);
+        }
+        Ok(proxy)
+    }
+
+    /// Ask the proxy, already connected over `stream`, to open a tunnel to
+    /// `host:port`. `Err` carries the reason the proxy refused.
+    pub async fn tunnel(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let authority = match host.contains(':') {
+            true => format!("[{host}]:{port}"),
+            false => format!("{host}:{port}"),
+        };
+        let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+        if let Some((user, password)) = self.auth.as_ref() {
+            let credentials =
+                base64::engine::general_purpose::STANDARD.encode(format!("{user}:{password}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+        }
+        request.push_str("\r\n");
+
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("failed to send CONNECT to the proxy: {e}"))?;
+
+        // read byte by byte, so nothing sent by the remote after the head is lost
+        let mut head = vec![];
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() >= MAX_CONNECT_RESPONSE {
+                return Err(String::from("the proxy sent an oversized CONNECT response"));
+            }
+            let byte = stream
+                .read_u8()
+                .await
+                .map_err(|e| format!("failed to read the CONNECT response of the proxy: {e}"))?;
+            head.push(byte);
+        }
+
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut resp = httparse::Response::new(&mut headers);
+        match resp.parse(&head).ok().and(resp.code) {
+            Some(status) if (200..300).contains(&status) => Ok(()),
+            Some(407) => Err(String::from("the proxy requires authentication (407)")),
+            Some(status) => Err(format!(
+                "the proxy refused to connect to {authority} ({status})"
+            )),
+            None => Err(String::from("the proxy sent an invalid CONNECT response")),
+        }
+    }
+}
+
+/// Upper case, then lower case, like curl
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_ascii_lowercase()))
+        .ok()
+}
+
+fn percent_decode(s: &str) -> String {
+    percent_encoding::percent_decode_str(s)
+        .decode_utf8_lossy()
+        .into_owned()
+}